@@ -0,0 +1,200 @@
+use serde::{Deserialize, Deserializer, Serialize, de::Error};
+
+// Mean earth radius, in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// A WGS84 coordinate, shared by stops, shapes and realtime vehicle positions
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+fn check_range(value: f64, limit: f64, name: &str) -> Result<f64, String> {
+    if value.is_finite() && (-limit..=limit).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("Invalid {}: {}", name, value))
+    }
+}
+
+impl LatLon {
+    pub fn new(lat: f64, lon: f64) -> Result<Self, String> {
+        Ok(Self {
+            lat: check_range(lat, 90.0, "latitude")?,
+            lon: check_range(lon, 180.0, "longitude")?,
+        })
+    }
+
+    /// Great-circle distance in meters, using the haversine formula
+    pub fn distance(&self, other: &Self) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+
+    /// Initial bearing from `self` towards `other`, in degrees clockwise from north (0-360)
+    pub fn bearing(&self, other: &Self) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlon = (other.lon - self.lon).to_radians();
+
+        let y = dlon.sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+        y.atan2(x).to_degrees().rem_euclid(360.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: LatLon,
+    pub max: LatLon,
+}
+
+impl BoundingBox {
+    /// The smallest box containing every point, or `None` if there are no points
+    pub fn from_points<I>(points: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = LatLon>,
+    {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        Some(points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |mut bbox, p| {
+                bbox.min.lat = bbox.min.lat.min(p.lat);
+                bbox.min.lon = bbox.min.lon.min(p.lon);
+                bbox.max.lat = bbox.max.lat.max(p.lat);
+                bbox.max.lon = bbox.max.lon.max(p.lon);
+                bbox
+            },
+        ))
+    }
+
+    /// A box containing every point within `radius` meters of `center`. Doesn't handle boxes that
+    /// cross the antimeridian
+    pub fn around(center: LatLon, radius: f64) -> Self {
+        let dlat = (radius / EARTH_RADIUS).to_degrees();
+        let dlon = dlat / center.lat.to_radians().cos().max(f64::EPSILON);
+
+        Self {
+            min: LatLon {
+                lat: (center.lat - dlat).max(-90.0),
+                lon: (center.lon - dlon).max(-180.0),
+            },
+            max: LatLon {
+                lat: (center.lat + dlat).min(90.0),
+                lon: (center.lon + dlon).min(180.0),
+            },
+        }
+    }
+
+    pub fn contains(&self, point: &LatLon) -> bool {
+        (self.min.lat..=self.max.lat).contains(&point.lat)
+            && (self.min.lon..=self.max.lon).contains(&point.lon)
+    }
+
+    pub fn center(&self) -> LatLon {
+        LatLon {
+            lat: (self.min.lat + self.max.lat) / 2.0,
+            lon: (self.min.lon + self.max.lon) / 2.0,
+        }
+    }
+}
+
+// Range-checked deserializers for the lat/lon columns of GTFS files
+pub(crate) fn deserialize_lat<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    check_range(f64::deserialize(deserializer)?, 90.0, "latitude").map_err(D::Error::custom)
+}
+
+pub(crate) fn deserialize_lon<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    check_range(f64::deserialize(deserializer)?, 180.0, "longitude").map_err(D::Error::custom)
+}
+
+pub(crate) fn deserialize_opt_lat<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<f64>::deserialize(deserializer)?
+        .map(|v| check_range(v, 90.0, "latitude").map_err(D::Error::custom))
+        .transpose()
+}
+
+pub(crate) fn deserialize_opt_lon<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<f64>::deserialize(deserializer)?
+        .map(|v| check_range(v, 180.0, "longitude").map_err(D::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        assert!(LatLon::new(40.643748, -74.073643).is_ok());
+        assert!(LatLon::new(90.0, 180.0).is_ok());
+        assert!(LatLon::new(90.1, 0.0).is_err());
+        assert!(LatLon::new(0.0, -180.5).is_err());
+        assert!(LatLon::new(f64::NAN, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_distance_and_bearing() {
+        // Times Sq-42 St to Grand Central-42 St
+        let times_sq = LatLon::new(40.755477, -73.987691).unwrap();
+        let grand_central = LatLon::new(40.751776, -73.976848).unwrap();
+
+        let distance = times_sq.distance(&grand_central);
+        assert!((distance - 995.0).abs() < 10.0, "{}", distance);
+        assert_eq!(times_sq.distance(&times_sq), 0.0);
+
+        let bearing = times_sq.bearing(&grand_central);
+        assert!((bearing - 115.0).abs() < 2.0, "{}", bearing);
+
+        let origin = LatLon::new(0.0, 0.0).unwrap();
+        assert!((origin.bearing(&LatLon::new(1.0, 0.0).unwrap()) - 0.0).abs() < 1e-9);
+        assert!((origin.bearing(&LatLon::new(0.0, 1.0).unwrap()) - 90.0).abs() < 1e-9);
+        assert!((origin.bearing(&LatLon::new(-1.0, 0.0).unwrap()) - 180.0).abs() < 1e-9);
+        assert!((origin.bearing(&LatLon::new(0.0, -1.0).unwrap()) - 270.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let points = [
+            LatLon::new(40.70, -74.01).unwrap(),
+            LatLon::new(40.88, -73.90).unwrap(),
+            LatLon::new(40.58, -73.82).unwrap(),
+        ];
+
+        let bbox = BoundingBox::from_points(points).unwrap();
+        assert_eq!(bbox.min, LatLon::new(40.58, -74.01).unwrap());
+        assert_eq!(bbox.max, LatLon::new(40.88, -73.82).unwrap());
+        assert!(points.iter().all(|p| bbox.contains(p)));
+        assert!(!bbox.contains(&LatLon::new(40.50, -73.90).unwrap()));
+        assert!(BoundingBox::from_points([]).is_none());
+
+        let center = bbox.center();
+        let around = BoundingBox::around(center, 1000.0);
+        let north = LatLon::new(center.lat + 0.0089, center.lon).unwrap();
+        let east = LatLon::new(center.lat, center.lon + 0.0118).unwrap();
+        assert!(around.contains(&north) && around.contains(&east));
+        assert!(!around.contains(&LatLon::new(center.lat + 0.0091, center.lon).unwrap()));
+        assert!(!around.contains(&LatLon::new(center.lat, center.lon + 0.0120).unwrap()));
+    }
+}
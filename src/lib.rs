@@ -1,5 +1,6 @@
 // TODO disable this
 #![allow(unused)]
 
+pub mod geo;
 pub mod realtime;
 pub mod schedule;
@@ -1,5 +1,7 @@
 use protobuf::Message;
-use protos::gtfs_realtime::FeedMessage;
+use protos::gtfs_realtime::{FeedMessage, Position};
+
+use crate::geo::LatLon;
 
 pub mod protos;
 
@@ -7,6 +9,17 @@ pub fn try_parse_bytes(bytes: &[u8]) -> Option<FeedMessage> {
     FeedMessage::parse_from_bytes(bytes).ok()
 }
 
+impl TryFrom<&Position> for LatLon {
+    type Error = String;
+
+    fn try_from(value: &Position) -> Result<Self, Self::Error> {
+        match (value.latitude, value.longitude) {
+            (Some(lat), Some(lon)) => LatLon::new(lat as f64, lon as f64),
+            _ => Err("Position is missing latitude or longitude".to_owned()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs::File, io::Read};
@@ -167,4 +180,19 @@ mod tests {
             num_stops = 0
         );
     }
+
+    #[test]
+    fn test_position_latlon() {
+        let mut position = Position::new();
+        assert!(LatLon::try_from(&position).is_err());
+
+        position.latitude = Some(40.64375);
+        position.longitude = Some(-74.07364);
+        let latlon = LatLon::try_from(&position).unwrap();
+        assert!((latlon.lat - 40.64375).abs() < 1e-5);
+        assert!((latlon.lon + 74.07364).abs() < 1e-5);
+
+        position.latitude = Some(91.0);
+        assert!(LatLon::try_from(&position).is_err());
+    }
 }
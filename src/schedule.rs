@@ -1,32 +1,62 @@
 pub mod agency;
+pub mod attributions;
 pub mod calendar;
+pub mod compact;
+pub mod date;
+pub mod error;
+pub mod fares;
+pub mod fares_v2;
+pub mod feed_info;
+pub mod flex;
+pub mod frequencies;
+pub mod loader;
+pub mod parallel;
+pub mod pathways;
+mod records;
 pub mod routes;
 pub mod shapes;
+pub mod source;
+pub mod stations;
 pub mod stop_times;
 pub mod stops;
+pub mod time;
 pub mod transfers;
+pub mod translations;
 pub mod trips;
 pub mod zip;
 
 pub mod mta;
 
 use std::{
-    collections::{HashMap, hash_map::Entry},
-    fs,
-    io::{Read, Seek},
-    path::Path,
+    collections::{HashMap, HashSet, hash_map::Entry},
+    io::Read,
 };
 
-use ::zip::read::ZipFile;
 use agency::Agency;
+use attributions::Attribution;
 use calendar::{Service, ServiceException};
+use date::ServiceDate;
+use fares::{FareAttribute, FareRule};
+use fares_v2::{
+    Area, FareLegRule, FareMedia, FareProduct, FareTransferRule, Network, RiderCategory,
+    RouteNetwork, StopArea, Timeframe,
+};
+use feed_info::FeedInfo;
+use flex::{BookingRule, Location, LocationGroup, LocationGroupStop};
+use frequencies::Frequency;
+use pathways::{Level, Pathway};
+use records::Records;
 use routes::Route;
 use shapes::{Shape, ShapePoint};
 use stop_times::StopTime;
 use stops::Stop;
 use transfers::Transfer;
+use translations::Translation;
 use trips::Trip;
 
+pub use error::{Error, ParseContext, ParseDiagnostic, ParseError, ParseMode, Result};
+pub use loader::ScheduleLoader;
+pub use source::{DirSource, FeedSource, LoadOptions};
 //pub use agency::Agency;
 //pub use calendar::{Activity, ExceptionType, Service, ServiceException};
 //pub use routes::{ContinuousType, Route, RouteType};
@@ -36,75 +66,75 @@ use trips::Trip;
 //pub use transfers::{Transfer, TransferType};
 //pub use trips::{BikeSupport, DirectionType, Trip, WheelchairAccessibility};
 
-macro_rules! parse_file {
-    ($tf:literal, $t:ty, $dir: ident) => {{
-        let mut res: Vec<$t> = Vec::new();
-        let mut path = $dir.as_ref().to_path_buf();
-        path.push($tf);
-        if Path::try_exists(&path).expect("Unable to check existence of file") {
-            let mut reader = csv::Reader::from_path(path).expect("Unable to read file");
-
-            for rec in reader.deserialize() {
-                res.push(rec.expect("Unable to parse file"));
-            }
-        }
-
-        res
-    }};
-}
-
 macro_rules! parse_reader {
-    (vec: $r:expr, $t:ty) => {{
+    (vec: $r:expr, $ctx:expr, $t:ty, $tf:literal) => {{
         let mut res: Vec<$t> = Vec::new();
-        let mut reader = csv::Reader::from_reader($r);
 
-        for rec in reader.deserialize() {
-            let rec: $t = if let Ok(x) = rec { x } else { continue };
+        for rec in Records::<$t, _>::new($r, $tf) {
+            let Some(rec) = $ctx.accept(rec)? else {
+                continue;
+            };
             res.push(rec);
         }
 
-        res
+        Ok(res)
     }};
-    (map: $r:expr, $kt:ty, $vt:ty, $kf:ident) => {{
+    (map: $r:expr, $ctx:expr, $kt:ty, $vt:ty, $kf:ident, $tf:literal) => {{
         let mut res: HashMap<$kt, $vt> = HashMap::new();
-        let mut reader = csv::Reader::from_reader($r);
 
-        for rec in reader.deserialize() {
-            let rec: $vt = if let Ok(x) = rec { x } else { continue };
+        for rec in Records::<$vt, _>::new($r, $tf) {
+            let Some(rec): Option<$vt> = $ctx.accept(rec)? else {
+                continue;
+            };
             res.insert(rec.$kf.clone(), rec);
         }
 
-        res
+        Ok(res)
+    }};
+    (group: $r:expr, $ctx:expr, $vt:ty, $kf:ident, $tf:literal) => {{
+        let mut res: HashMap<String, Vec<$vt>> = HashMap::new();
+
+        for rec in Records::<$vt, _>::new($r, $tf) {
+            let Some(rec): Option<$vt> = $ctx.accept(rec)? else {
+                continue;
+            };
+            res.entry(rec.$kf.clone()).or_default().push(rec);
+        }
+
+        Ok(res)
     }};
-    (cmap: $r:expr, $kt:ty, $vt:ty, $kf:ident, $rec:ident, $cond:expr) => {{
+    (cmap: $r:expr, $ctx:expr, $kt:ty, $vt:ty, $kf:ident, $tf:literal, $rec:ident, $cond:expr) => {{
         let mut res: HashMap<$kt, $vt> = HashMap::new();
-        let mut reader = csv::Reader::from_reader($r);
 
-        for rec in reader.deserialize() {
-            let $rec: $vt = if let Ok(x) = rec { x } else { continue };
+        for rec in Records::<$vt, _>::new($r, $tf) {
+            let Some($rec): Option<$vt> = $ctx.accept(rec)? else {
+                continue;
+            };
             if $cond {
                 res.insert($rec.$kf.clone(), $rec);
             }
         }
 
-        res
+        Ok(res)
     }};
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 /// This struct stores things in maps which allows for far more efficient parsing into a natural,
 /// hierarchical format
 pub struct Schedule {
     // Agencies is tiny, no need for map
     pub agencies: Vec<Agency>,
+    // feed_info.txt has at most one record, and is optional
+    pub feed_info: Option<FeedInfo>,
     // Indexed by stop_id
     pub stops: HashMap<String, Stop>,
-    // Indexed by trip_id, then stop_sequence
-    pub stop_times: HashMap<String, HashMap<u32, StopTime>>,
+    // Indexed by trip_id, ordered by stop_sequence
+    pub stop_times: HashMap<String, Vec<StopTime>>,
     // Indexed by service_id
     pub services: HashMap<String, Service>,
     // Indexed by service_id, then date
-    pub service_exceptions: HashMap<String, HashMap<String, ServiceException>>,
+    pub service_exceptions: HashMap<String, HashMap<ServiceDate, ServiceException>>,
     // Indexed by shape_id
     pub shapes: HashMap<String, Shape>,
     // Indexed by from_stop_id
@@ -113,97 +143,177 @@ pub struct Schedule {
     pub routes: HashMap<String, Route>,
     // Indexed by trip_id
     pub trips: HashMap<String, Trip>,
+    // Indexed by trip_id, empty if the feed has no frequencies.txt
+    pub frequencies: HashMap<String, Vec<Frequency>>,
+    // Indexed by fare_id, empty if the feed has no fare_attributes.txt
+    pub fare_attributes: HashMap<String, FareAttribute>,
+    // Indexed by fare_id, empty if the feed has no fare_rules.txt
+    pub fare_rules: HashMap<String, Vec<FareRule>>,
+    // Fares v2 files are all optional, and empty when missing
+    // Indexed by fare_media_id
+    pub fare_media: HashMap<String, FareMedia>,
+    // Indexed by fare_product_id, one entry per rider category and fare media
+    pub fare_products: HashMap<String, Vec<FareProduct>>,
+    pub fare_leg_rules: Vec<FareLegRule>,
+    pub fare_transfer_rules: Vec<FareTransferRule>,
+    // Indexed by area_id
+    pub areas: HashMap<String, Area>,
+    // Indexed by stop_id
+    pub stop_areas: HashMap<String, Vec<StopArea>>,
+    // Indexed by network_id
+    pub networks: HashMap<String, Network>,
+    // Indexed by route_id
+    pub route_networks: HashMap<String, RouteNetwork>,
+    // Indexed by timeframe_group_id
+    pub timeframes: HashMap<String, Vec<Timeframe>>,
+    // Indexed by rider_category_id
+    pub rider_categories: HashMap<String, RiderCategory>,
+    // Indexed by pathway_id, empty if the feed has no pathways.txt
+    pub pathways: HashMap<String, Pathway>,
+    // Indexed by level_id, empty if the feed has no levels.txt
+    pub levels: HashMap<String, Level>,
+    // Indexed by table_name, empty if the feed has no translations.txt
+    pub translations: HashMap<String, Vec<Translation>>,
+    // GTFS-Flex files, all optional
+    // Indexed by the feature id in locations.geojson
+    pub locations: HashMap<String, Location>,
+    // Indexed by location_group_id
+    pub location_groups: HashMap<String, LocationGroup>,
+    // Indexed by location_group_id
+    pub location_group_stops: HashMap<String, Vec<LocationGroupStop>>,
+    // Indexed by booking_rule_id
+    pub booking_rules: HashMap<String, BookingRule>,
+    // Attributions is tiny, no need for map
+    pub attributions: Vec<Attribution>,
+    // Records skipped while parsing in lenient mode, always empty in strict mode
+    pub diagnostics: Vec<ParseDiagnostic>,
 }
 
-pub fn parse_agencies<R>(reader: R) -> Vec<Agency>
+pub fn parse_agencies<R>(reader: R, ctx: &mut ParseContext) -> Result<Vec<Agency>>
 where
     R: Read,
 {
-    parse_reader!(vec: reader, Agency)
+    parse_reader!(vec: reader, ctx, Agency, "agency.txt")
 }
-pub fn parse_stops<R>(reader: R) -> HashMap<String, Stop>
+pub fn parse_feed_info<R>(reader: R, ctx: &mut ParseContext) -> Result<Option<FeedInfo>>
 where
     R: Read,
 {
-    parse_reader!(map: reader, String, Stop, stop_id)
+    let feed_info: Result<Vec<FeedInfo>> =
+        parse_reader!(vec: reader, ctx, FeedInfo, "feed_info.txt");
+    Ok(feed_info?.into_iter().next())
+}
+pub fn parse_stops<R>(reader: R, ctx: &mut ParseContext) -> Result<HashMap<String, Stop>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, Stop, stop_id, "stops.txt")
 }
 pub fn parse_services<R>(
     reader: R,
-    date_bounds: Option<(&String, &String)>,
-) -> HashMap<String, Service>
+    date_bounds: Option<(ServiceDate, ServiceDate)>,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Service>>
 where
     R: Read,
 {
     match date_bounds {
         Some((start, end)) => {
-            parse_reader!(cmap: reader, String, Service, service_id, service, &service.start_date <= end && &service.end_date >= start)
+            parse_reader!(cmap: reader, ctx, String, Service, service_id, "calendar.txt", service, service.start_date <= end && service.end_date >= start)
         }
-        None => parse_reader!(map: reader, String, Service, service_id),
+        None => parse_reader!(map: reader, ctx, String, Service, service_id, "calendar.txt"),
     }
 }
 pub fn parse_service_exceptions<R>(
     reader: R,
-    date_bounds: Option<(&String, &String)>,
-) -> HashMap<String, HashMap<String, ServiceException>>
+    date_bounds: Option<(ServiceDate, ServiceDate)>,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, HashMap<ServiceDate, ServiceException>>>
 where
     R: Read,
 {
-    let mut service_exceptions: HashMap<String, HashMap<String, ServiceException>> = HashMap::new();
-    let mut csv_reader = csv::Reader::from_reader(reader);
-    for rec in csv_reader.deserialize() {
-        let rec: ServiceException = if let Ok(x) = rec { x } else { continue };
+    let mut service_exceptions: HashMap<String, HashMap<ServiceDate, ServiceException>> =
+        HashMap::new();
+    for rec in Records::<ServiceException, _>::new(reader, "calendar_dates.txt") {
+        let Some(rec) = ctx.accept(rec)? else {
+            continue;
+        };
 
-        if let Some((start, end)) = date_bounds {
-            if &rec.date < start || &rec.date > end {
-                continue;
-            }
+        if let Some((start, end)) = date_bounds
+            && (rec.date < start || rec.date > end)
+        {
+            continue;
         }
 
         match service_exceptions.entry(rec.service_id.clone()) {
             Entry::Occupied(mut e) => {
-                e.get_mut().insert(rec.date.clone(), rec);
+                e.get_mut().insert(rec.date, rec);
             }
             Entry::Vacant(e) => {
-                let mut new_entry: HashMap<String, ServiceException> = HashMap::new();
-                new_entry.insert(rec.date.clone(), rec);
+                let mut new_entry: HashMap<ServiceDate, ServiceException> = HashMap::new();
+                new_entry.insert(rec.date, rec);
                 e.insert(new_entry);
             }
         }
     }
 
-    service_exceptions
+    Ok(service_exceptions)
 }
-pub fn parse_routes<R>(reader: R) -> HashMap<String, Route>
+pub fn parse_routes<R>(reader: R, ctx: &mut ParseContext) -> Result<HashMap<String, Route>>
 where
     R: Read,
 {
-    parse_reader!(map: reader, String, Route, route_id)
+    parse_reader!(map: reader, ctx, String, Route, route_id, "routes.txt")
 }
 pub fn parse_trips<R>(
     reader: R,
     services: &HashMap<String, Service>,
-    service_exceptions: &HashMap<String, HashMap<String, ServiceException>>,
-) -> HashMap<String, Trip>
+    service_exceptions: &HashMap<String, HashMap<ServiceDate, ServiceException>>,
+    date_bounds: Option<(ServiceDate, ServiceDate)>,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Trip>>
 where
     R: Read,
 {
-    parse_reader!(cmap: reader, String, Trip, trip_id, trip, services.contains_key(&trip.service_id) || service_exceptions.contains_key(&trip.service_id))
+    // Only keep services that actually run on at least one day within the bounds
+    let active_services: HashSet<&str> = services
+        .keys()
+        .chain(service_exceptions.keys())
+        .map(String::as_str)
+        .filter(|id| match date_bounds {
+            Some((start, end)) => start
+                .iter_to(end)
+                .any(|d| calendar::is_service_active(services, service_exceptions, id, d)),
+            None => true,
+        })
+        .collect();
+
+    parse_reader!(cmap: reader, ctx, String, Trip, trip_id, "trips.txt", trip, active_services.contains(trip.service_id.as_str()))
 }
-pub fn parse_shapes<R>(reader: R) -> HashMap<String, Shape>
+pub fn parse_shapes<R>(
+    reader: R,
+    keep_sequences: bool,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Shape>>
 where
     R: Read,
 {
-    let shape_points: Vec<ShapePoint> = parse_reader!(vec: reader, ShapePoint);
-    Shape::process_points(&shape_points)
+    let shape_points: Result<Vec<ShapePoint>> =
+        parse_reader!(vec: reader, ctx, ShapePoint, "shapes.txt");
+    Ok(Shape::process_points(&shape_points?, keep_sequences))
 }
-pub fn parse_transfers<R>(reader: R) -> HashMap<String, Vec<Transfer>>
+pub fn parse_transfers<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Vec<Transfer>>>
 where
     R: Read,
 {
     let mut transfers: HashMap<String, Vec<Transfer>> = HashMap::new();
-    let mut csv_reader = csv::Reader::from_reader(reader);
-    for rec in csv_reader.deserialize() {
-        let rec: Transfer = if let Ok(x) = rec { x } else { continue };
+    for rec in Records::<Transfer, _>::new(reader, "transfers.txt") {
+        let Some(rec) = ctx.accept(rec)? else {
+            continue;
+        };
         let from_stop_id: String = if let Some(x) = rec.from_stop_id.clone() {
             x
         } else {
@@ -220,127 +330,499 @@ where
         }
     }
 
-    transfers
+    Ok(transfers)
 }
-pub fn parse_stop_times<R>(
+pub fn parse_frequencies<R>(
     reader: R,
     trips: &HashMap<String, Trip>,
-) -> HashMap<String, HashMap<u32, StopTime>>
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Vec<Frequency>>>
 where
     R: Read,
 {
-    let mut stop_times: HashMap<String, HashMap<u32, StopTime>> = HashMap::new();
-    let mut csv_reader = csv::Reader::from_reader(reader);
-    for rec in csv_reader.deserialize() {
-        let rec: StopTime = if let Ok(x) = rec { x } else { continue };
+    let mut frequencies: HashMap<String, Vec<Frequency>> = HashMap::new();
+    for rec in Records::<Frequency, _>::new(reader, "frequencies.txt") {
+        let Some(rec) = ctx.accept(rec)? else {
+            continue;
+        };
         if !trips.contains_key(&rec.trip_id) {
             continue;
         }
-        match stop_times.entry(rec.trip_id.clone()) {
+
+        match frequencies.entry(rec.trip_id.clone()) {
             Entry::Occupied(mut e) => {
-                e.get_mut().insert(rec.stop_sequence, rec);
+                e.get_mut().push(rec);
             }
             Entry::Vacant(e) => {
-                let mut new_entry: HashMap<u32, StopTime> = HashMap::new();
-                new_entry.insert(rec.stop_sequence, rec);
-                e.insert(new_entry);
+                e.insert(vec![rec]);
             }
         }
     }
 
-    stop_times
-}
-
-impl Schedule {
-    pub fn from_readers<R>(
-        agency_reader: R,
-        stop_reader: R,
-        stop_time_reader: R,
-        service_reader: R,
-        service_exception_reader: R,
-        shape_reader: R,
-        transfer_reader: R,
-        route_reader: R,
-        trip_reader: R,
-        date_bounds: Option<(&String, &String)>,
-    ) -> Option<Self>
-    where
-        R: Read,
-    {
-        // It's important that they get called in order (more or less)
-        let agencies = parse_agencies(agency_reader);
-        let stops = parse_stops(stop_reader);
-        let services = parse_services(service_reader, date_bounds);
-        let service_exceptions = parse_service_exceptions(service_exception_reader, date_bounds);
-        let routes = parse_routes(route_reader);
-        let trips = parse_trips(trip_reader, &services, &service_exceptions);
-        let shapes = parse_shapes(shape_reader);
-        let transfers = parse_transfers(transfer_reader);
-        let stop_times = parse_stop_times(stop_time_reader, &trips);
-
-        Some(Self {
-            agencies,
-            routes,
-            transfers,
-            trips,
-            stop_times,
-            service_exceptions,
-            shapes,
-            stops,
-            services,
-        })
+    Ok(frequencies)
+}
+pub fn parse_fare_attributes<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, FareAttribute>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, FareAttribute, fare_id, "fare_attributes.txt")
+}
+pub fn parse_fare_rules<R>(
+    reader: R,
+    fare_attributes: &HashMap<String, FareAttribute>,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Vec<FareRule>>>
+where
+    R: Read,
+{
+    let mut fare_rules: HashMap<String, Vec<FareRule>> = HashMap::new();
+    for rec in Records::<FareRule, _>::new(reader, "fare_rules.txt") {
+        let Some(rec) = ctx.accept(rec)? else {
+            continue;
+        };
+        if !fare_attributes.contains_key(&rec.fare_id) {
+            continue;
+        }
+
+        match fare_rules.entry(rec.fare_id.clone()) {
+            Entry::Occupied(mut e) => {
+                e.get_mut().push(rec);
+            }
+            Entry::Vacant(e) => {
+                e.insert(vec![rec]);
+            }
+        }
     }
+
+    Ok(fare_rules)
+}
+pub fn parse_fare_media<R>(reader: R, ctx: &mut ParseContext) -> Result<HashMap<String, FareMedia>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, FareMedia, fare_media_id, "fare_media.txt")
+}
+pub fn parse_fare_products<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Vec<FareProduct>>>
+where
+    R: Read,
+{
+    parse_reader!(group: reader, ctx, FareProduct, fare_product_id, "fare_products.txt")
+}
+pub fn parse_fare_leg_rules<R>(reader: R, ctx: &mut ParseContext) -> Result<Vec<FareLegRule>>
+where
+    R: Read,
+{
+    parse_reader!(vec: reader, ctx, FareLegRule, "fare_leg_rules.txt")
+}
+pub fn parse_fare_transfer_rules<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<Vec<FareTransferRule>>
+where
+    R: Read,
+{
+    parse_reader!(vec: reader, ctx, FareTransferRule, "fare_transfer_rules.txt")
+}
+pub fn parse_areas<R>(reader: R, ctx: &mut ParseContext) -> Result<HashMap<String, Area>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, Area, area_id, "areas.txt")
+}
+pub fn parse_stop_areas<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Vec<StopArea>>>
+where
+    R: Read,
+{
+    parse_reader!(group: reader, ctx, StopArea, stop_id, "stop_areas.txt")
+}
+pub fn parse_networks<R>(reader: R, ctx: &mut ParseContext) -> Result<HashMap<String, Network>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, Network, network_id, "networks.txt")
+}
+pub fn parse_route_networks<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, RouteNetwork>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, RouteNetwork, route_id, "route_networks.txt")
+}
+pub fn parse_timeframes<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Vec<Timeframe>>>
+where
+    R: Read,
+{
+    parse_reader!(group: reader, ctx, Timeframe, timeframe_group_id, "timeframes.txt")
+}
+pub fn parse_rider_categories<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, RiderCategory>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, RiderCategory, rider_category_id, "rider_categories.txt")
+}
+pub fn parse_pathways<R>(reader: R, ctx: &mut ParseContext) -> Result<HashMap<String, Pathway>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, Pathway, pathway_id, "pathways.txt")
+}
+pub fn parse_levels<R>(reader: R, ctx: &mut ParseContext) -> Result<HashMap<String, Level>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, Level, level_id, "levels.txt")
+}
+pub fn parse_translations<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Vec<Translation>>>
+where
+    R: Read,
+{
+    parse_reader!(group: reader, ctx, Translation, table_name, "translations.txt")
+}
+pub fn parse_locations<R>(reader: R, ctx: &mut ParseContext) -> Result<HashMap<String, Location>>
+where
+    R: Read,
+{
+    Ok(flex::read_locations(reader, ctx)?
+        .into_iter()
+        .map(|l| (l.location_id.clone(), l))
+        .collect())
+}
+pub fn parse_location_groups<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, LocationGroup>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, LocationGroup, location_group_id, "location_groups.txt")
 }
+pub fn parse_location_group_stops<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Vec<LocationGroupStop>>>
+where
+    R: Read,
+{
+    parse_reader!(group: reader, ctx, LocationGroupStop, location_group_id, "location_group_stops.txt")
+}
+pub fn parse_booking_rules<R>(
+    reader: R,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, BookingRule>>
+where
+    R: Read,
+{
+    parse_reader!(map: reader, ctx, String, BookingRule, booking_rule_id, "booking_rules.txt")
+}
+pub fn parse_attributions<R>(reader: R, ctx: &mut ParseContext) -> Result<Vec<Attribution>>
+where
+    R: Read,
+{
+    parse_reader!(vec: reader, ctx, Attribution, "attributions.txt")
+}
+// Orders a trip's stop times, given in file order, by stop_sequence. The sort is stable, so of
+// several stop times with the same stop_sequence the last one in the file is kept
+pub(crate) fn sort_stop_times(stop_times: &mut Vec<StopTime>) {
+    stop_times.sort_by_key(|st| st.stop_sequence);
+    stop_times.dedup_by(|later, earlier| {
+        let duplicate = later.stop_sequence == earlier.stop_sequence;
+        if duplicate {
+            std::mem::swap(later, earlier);
+        }
+        duplicate
+    });
+}
+pub fn parse_stop_times<R>(
+    reader: R,
+    trips: &HashMap<String, Trip>,
+    ctx: &mut ParseContext,
+) -> Result<HashMap<String, Vec<StopTime>>>
+where
+    R: Read,
+{
+    let mut stop_times: HashMap<String, Vec<StopTime>> = HashMap::new();
+    for rec in Records::<StopTime, _>::new(reader, "stop_times.txt") {
+        let Some(rec) = ctx.accept(rec)? else {
+            continue;
+        };
+        if !trips.contains_key(&rec.trip_id) {
+            continue;
+        }
+        stop_times.entry(rec.trip_id.clone()).or_default().push(rec);
+    }
+    for trip_stop_times in stop_times.values_mut() {
+        sort_stop_times(trip_stop_times);
+    }
+
+    Ok(stop_times)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::fs::File;
+    use std::io;
 
-    use crate::schedule::calendar::ExceptionType;
+    use crate::schedule::{calendar::ExceptionType, date::Weekday};
 
     use super::*;
 
-    macro_rules! setup_new_schedule {
-        ($bounds:expr) => {{
-            let agency_reader = File::open("./test_data/schedule/agency.txt").unwrap();
-            let stop_reader = File::open("./test_data/schedule/stops.txt").unwrap();
-            let stop_time_reader = File::open("./test_data/schedule/stop_times.txt").unwrap();
-            let service_reader = File::open("./test_data/schedule/calendar.txt").unwrap();
-            let service_exception_reader =
-                File::open("./test_data/schedule/calendar_dates.txt").unwrap();
-            let shape_reader = File::open("./test_data/schedule/shapes.txt").unwrap();
-            let transfer_reader = File::open("./test_data/schedule/transfers.txt").unwrap();
-            let route_reader = File::open("./test_data/schedule/routes.txt").unwrap();
-            let trip_reader = File::open("./test_data/schedule/trips.txt").unwrap();
-
-            Schedule::from_readers(
-                agency_reader,
-                stop_reader,
-                stop_time_reader,
-                service_reader,
-                service_exception_reader,
-                shape_reader,
-                transfer_reader,
-                route_reader,
-                trip_reader,
-                $bounds,
+    fn setup_new_schedule(bounds: Option<(ServiceDate, ServiceDate)>) -> Result<Schedule> {
+        let mut loader = ScheduleLoader::new().mode(ParseMode::Strict);
+        if let Some((start, end)) = bounds {
+            loader = loader.date_bounds(start, end);
+        }
+
+        loader.load_dir("./test_data/schedule")
+    }
+
+    // Counts the trips and stop times running on `date` straight from the MTA files, so the
+    // loader's date filtering is checked against something it didn't compute
+    fn raw_counts(date: &str) -> (usize, usize) {
+        fn read(name: &str) -> (csv::StringRecord, Vec<csv::StringRecord>) {
+            let mut reader =
+                csv::Reader::from_path(format!("./test_data/schedule/{}", name)).unwrap();
+            let headers = reader.headers().unwrap().clone();
+            let records = reader.records().map(|r| r.unwrap()).collect();
+            (headers, records)
+        }
+        fn col(headers: &csv::StringRecord, name: &str) -> usize {
+            headers.iter().position(|h| h == name).unwrap()
+        }
+
+        let day = match date.parse::<ServiceDate>().unwrap().weekday() {
+            Weekday::Monday => "monday",
+            Weekday::Tuesday => "tuesday",
+            Weekday::Wednesday => "wednesday",
+            Weekday::Thursday => "thursday",
+            Weekday::Friday => "friday",
+            Weekday::Saturday => "saturday",
+            Weekday::Sunday => "sunday",
+        };
+
+        let mut services: HashSet<String> = HashSet::new();
+        let (headers, records) = read("calendar.txt");
+        let (id, runs) = (col(&headers, "service_id"), col(&headers, day));
+        let (start, end) = (col(&headers, "start_date"), col(&headers, "end_date"));
+        for rec in records {
+            if &rec[runs] == "1" && &rec[start] <= date && date <= &rec[end] {
+                services.insert(rec[id].to_owned());
+            }
+        }
+        let (headers, records) = read("calendar_dates.txt");
+        let (id, on, kind) = (
+            col(&headers, "service_id"),
+            col(&headers, "date"),
+            col(&headers, "exception_type"),
+        );
+        for rec in records.iter().filter(|rec| &rec[on] == date) {
+            match &rec[kind] {
+                "1" => services.insert(rec[id].to_owned()),
+                _ => services.remove(&rec[id]),
+            };
+        }
+
+        let (headers, records) = read("trips.txt");
+        let (id, service) = (col(&headers, "trip_id"), col(&headers, "service_id"));
+        let trips: HashSet<String> = records
+            .iter()
+            .filter(|rec| services.contains(&rec[service]))
+            .map(|rec| rec[id].to_owned())
+            .collect();
+
+        let (headers, records) = read("stop_times.txt");
+        let id = col(&headers, "trip_id");
+        let stop_times = records
+            .iter()
+            .filter(|rec| trips.contains(&rec[id]))
+            .count();
+
+        (trips.len(), stop_times)
+    }
+
+    #[test]
+    fn test_parse_error_context() {
+        let data = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250101,20251231\nSunday,0,0,0,0,0,0,1,20250101,2025-12-31\n";
+
+        let err = parse_services(data.as_bytes(), None, &mut ParseContext::strict()).unwrap_err();
+        let Error::Parse(err) = err else {
+            panic!("Expected parse error, got {:?}", err)
+        };
+
+        assert_eq!(err.file, "calendar.txt");
+        assert_eq!(err.record, Some(2));
+        assert_eq!(err.line, Some(3));
+        assert_eq!(err.column, Some("end_date".to_owned()));
+        assert_eq!(err.value, Some("2025-12-31".to_owned()));
+        assert_eq!(err.message, "Invalid ServiceDate: 2025-12-31");
+    }
+
+    #[test]
+    fn test_parse_error_unequal_lengths() {
+        let data = "agency_name,agency_url,agency_timezone\nMTA,http://www.mta.info\n";
+
+        let err = parse_agencies(data.as_bytes(), &mut ParseContext::strict()).unwrap_err();
+        let Error::Parse(err) = err else {
+            panic!("Expected parse error, got {:?}", err)
+        };
+
+        assert_eq!(err.file, "agency.txt");
+        assert_eq!(err.record, Some(1));
+        assert_eq!(err.column, None);
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let data = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250101,20251231\nSaturday,0,0,0,0,0,1,0,20250101,2025-12-31\nSunday,0,0,0,0,0,0,1,20250101,20251231\n";
+
+        let mut ctx = ParseContext::lenient();
+        let services = parse_services(data.as_bytes(), None, &mut ctx).unwrap();
+
+        assert_eq!(services.len(), 2);
+        assert!(services.contains_key("Weekday") && services.contains_key("Sunday"));
+        assert_eq!(ctx.diagnostics.len(), 1);
+        assert_eq!(ctx.diagnostics[0].file, "calendar.txt");
+        assert_eq!(ctx.diagnostics[0].record, Some(2));
+        assert_eq!(ctx.diagnostics[0].value, Some("2025-12-31".to_owned()));
+    }
+
+    #[test]
+    fn test_load_modes() {
+        let agencies =
+            "agency_name,agency_url,agency_timezone\nMTA,http://www.mta.info,America/New_York\n";
+        let stops = "stop_id,stop_name\n101,Van Cortlandt Park-242 St\n";
+        let services = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250101,20251231\n";
+        let service_exceptions = "service_id,date,exception_type\n";
+        let routes = "route_id,route_type\n1,1\n";
+        let trips = "route_id,trip_id,service_id\n1,a,Weekday\n1,b,Weekday\n1,c\n";
+        let stop_times = "trip_id,stop_id,stop_sequence\na,101,1\nb,101,x\n";
+        let shapes = "shape_id,shape_pt_sequence,shape_pt_lat,shape_pt_lon\n";
+        let transfers = "from_stop_id,to_stop_id,transfer_type\n";
+
+        macro_rules! from_strs {
+            ($mode:expr) => {
+                ScheduleLoader::new()
+                    .file("agency.txt", agencies.as_bytes())
+                    .file("stops.txt", stops.as_bytes())
+                    .file("stop_times.txt", stop_times.as_bytes())
+                    .file("calendar.txt", services.as_bytes())
+                    .file("calendar_dates.txt", service_exceptions.as_bytes())
+                    .file("shapes.txt", shapes.as_bytes())
+                    .file("transfers.txt", transfers.as_bytes())
+                    .file("routes.txt", routes.as_bytes())
+                    .file("trips.txt", trips.as_bytes())
+                    .mode($mode)
+                    .load()
+            };
+        }
+
+        let err = from_strs!(ParseMode::Strict).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse(ParseError {
+                file: "trips.txt",
+                ..
+            })
+        ));
+
+        let schedule = from_strs!(ParseMode::Lenient).unwrap();
+        assert_eq!(schedule.trips.len(), 2);
+        assert_eq!(schedule.stop_times.len(), 1);
+        assert_eq!(
+            schedule
+                .diagnostics
+                .iter()
+                .map(|d| (d.file, d.record))
+                .collect::<Vec<_>>(),
+            vec![("trips.txt", Some(3)), ("stop_times.txt", Some(2))]
+        );
+    }
+
+    #[test]
+    fn test_load_io_error() {
+        // Hands out the first rows, then fails as a truncated download or bad disk would
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection reset",
+                ))
+            }
+        }
+
+        let stops = "stop_id,stop_name\n101,Van Cortlandt Park-242 St\n103,238 St\n".as_bytes();
+        let err = parse_stops(stops.chain(Failing), &mut ParseContext::lenient()).unwrap_err();
+        assert!(matches!(err, Error::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_parse_trips_date_bounds() {
+        let services = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250101,20251231\nSunday,0,0,0,0,0,0,1,20250101,20251231\n";
+        let service_exceptions = "service_id,date,exception_type\nWeekday,20250217,2\nSunday,20250217,1\nSpecial,20250218,1\n";
+        let trips = "route_id,trip_id,service_id\n1,wkd,Weekday\n1,sun,Sunday\n1,special,Special\n";
+
+        let mut ctx = ParseContext::strict();
+        let services = parse_services(services.as_bytes(), None, &mut ctx).unwrap();
+        let service_exceptions =
+            parse_service_exceptions(service_exceptions.as_bytes(), None, &mut ctx).unwrap();
+
+        let trip_ids = |start: &str, end: &str| {
+            let bounds = Some((start.parse().unwrap(), end.parse().unwrap()));
+            let trips = parse_trips(
+                trips.as_bytes(),
+                &services,
+                &service_exceptions,
+                bounds,
+                &mut ParseContext::strict(),
             )
-        }};
+            .unwrap();
+            let mut ids: Vec<String> = trips.into_keys().collect();
+            ids.sort();
+            ids
+        };
+
+        // Sunday
+        assert_eq!(trip_ids("20250216", "20250216"), vec!["sun"]);
+        // Presidents' Day, running a Sunday schedule
+        assert_eq!(trip_ids("20250217", "20250217"), vec!["sun"]);
+        assert_eq!(trip_ids("20250218", "20250218"), vec!["special", "wkd"]);
+        assert_eq!(trip_ids("20250219", "20250219"), vec!["wkd"]);
+        assert_eq!(
+            trip_ids("20250216", "20250219"),
+            vec!["special", "sun", "wkd"]
+        );
     }
 
     #[test]
     #[ignore]
-    fn test_from_readers_full() {
-        let schedule = setup_new_schedule!(None).unwrap();
+    fn test_load_full() {
+        let schedule = setup_new_schedule(None).unwrap();
 
         assert_eq!(schedule.agencies.len(), 1);
         assert_eq!(schedule.services.len(), 71);
         assert_eq!(schedule.stops.len(), 1497);
         assert_eq!(
-            schedule
-                .stop_times
-                .values()
-                .flat_map(HashMap::values)
-                .count(),
+            schedule.stop_times.values().map(Vec::len).sum::<usize>(),
             2_339_542
         );
         assert_eq!(
@@ -353,11 +835,7 @@ mod tests {
         );
         assert_eq!(schedule.shapes.len(), 311);
         assert_eq!(
-            schedule
-                .transfers
-                .values()
-                .map(Vec::len)
-                .fold(0, |a, b| a + b),
+            schedule.transfers.values().map(Vec::len).sum::<usize>(),
             616
         );
         assert_eq!(schedule.routes.len(), 30);
@@ -366,19 +844,16 @@ mod tests {
 
     #[test]
     #[ignore]
-    fn test_from_readers_abbrev() {
-        let (start, end) = ("20250301".to_owned(), "20250401".to_owned());
-        let schedule = setup_new_schedule!(Some((&start, &end))).unwrap();
+    fn test_load_abbrev() {
+        let (start, end): (ServiceDate, ServiceDate) =
+            ("20250301".parse().unwrap(), "20250401".parse().unwrap());
+        let schedule = setup_new_schedule(Some((start, end))).unwrap();
 
         assert_eq!(schedule.agencies.len(), 1);
         assert_eq!(schedule.services.len(), 71);
         assert_eq!(schedule.stops.len(), 1497);
         assert_eq!(
-            schedule
-                .stop_times
-                .values()
-                .flat_map(HashMap::values)
-                .count(),
+            schedule.stop_times.values().map(Vec::len).sum::<usize>(),
             1_914_369
         );
         assert_eq!(
@@ -391,11 +866,7 @@ mod tests {
         );
         assert_eq!(schedule.shapes.len(), 311);
         assert_eq!(
-            schedule
-                .transfers
-                .values()
-                .map(Vec::len)
-                .fold(0, |a, b| a + b),
+            schedule.transfers.values().map(Vec::len).sum::<usize>(),
             616
         );
         assert_eq!(schedule.routes.len(), 30);
@@ -415,29 +886,29 @@ mod tests {
                     || schedule.service_exceptions.contains_key(&trip.service_id)
             );
         }
-        for (trip_id, stop_time_map) in schedule.stop_times {
-            for (stop_seq, stop_time) in stop_time_map {
-                assert!(schedule.trips.contains_key(&trip_id));
-            }
+        for (trip_id, trip_stop_times) in schedule.stop_times {
+            assert!(schedule.trips.contains_key(&trip_id));
+            assert!(trip_stop_times.is_sorted_by_key(|st| st.stop_sequence));
         }
     }
 
     #[test]
     #[ignore]
-    fn test_from_readers_oneday() {
-        let (start, end) = ("20250217".to_owned(), "20250217".to_owned());
-        let schedule = setup_new_schedule!(Some((&start, &end))).unwrap();
+    fn test_load_oneday() {
+        let (start, end): (ServiceDate, ServiceDate) =
+            ("20250217".parse().unwrap(), "20250217".parse().unwrap());
+        let schedule = setup_new_schedule(Some((start, end))).unwrap();
 
         assert_eq!(schedule.agencies.len(), 1);
         assert_eq!(schedule.services.len(), 71);
         assert_eq!(schedule.stops.len(), 1497);
+        // Presidents' Day, weekday services are removed so this is well below the 562,256 stop
+        // times that belong to every service with a calendar entry covering the date
+        let (trips, stop_times) = raw_counts("20250217");
+        assert!(trips > 0 && stop_times < 562_256);
         assert_eq!(
-            schedule
-                .stop_times
-                .values()
-                .flat_map(HashMap::values)
-                .count(),
-            562_256
+            schedule.stop_times.values().map(Vec::len).sum::<usize>(),
+            stop_times
         );
         assert_eq!(
             schedule
@@ -452,7 +923,7 @@ mod tests {
             schedule
                 .service_exceptions
                 .values()
-                .map(|m| m.get("20250217"))
+                .map(|m| m.get(&start))
                 .filter(|se| matches!(
                     se,
                     Some(ServiceException {
@@ -476,15 +947,11 @@ mod tests {
         );
         assert_eq!(schedule.shapes.len(), 311);
         assert_eq!(
-            schedule
-                .transfers
-                .values()
-                .map(Vec::len)
-                .fold(0, |a, b| a + b),
+            schedule.transfers.values().map(Vec::len).sum::<usize>(),
             616
         );
         assert_eq!(schedule.routes.len(), 30);
-        assert_eq!(schedule.trips.len(), 20302);
+        assert_eq!(schedule.trips.len(), trips);
 
         for (service_id, service) in schedule.services.iter() {
             assert!(service.start_date <= end && service.end_date >= start);
@@ -495,15 +962,62 @@ mod tests {
             }
         }
         for (trip_id, trip) in schedule.trips.iter() {
-            assert!(
-                schedule.services.contains_key(&trip.service_id)
-                    || schedule.service_exceptions.contains_key(&trip.service_id)
-            );
+            assert!(schedule.is_service_active(&trip.service_id, start));
         }
-        for (trip_id, stop_time_map) in schedule.stop_times {
-            for (stop_seq, stop_time) in stop_time_map {
-                assert!(schedule.trips.contains_key(&trip_id));
-            }
+        for (trip_id, trip_stop_times) in schedule.stop_times {
+            assert!(schedule.trips.contains_key(&trip_id));
+            assert!(trip_stop_times.is_sorted_by_key(|st| st.stop_sequence));
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn test_load_sunday_vs_weekday() {
+        let sunday: ServiceDate = "20250216".parse().unwrap();
+        let tuesday: ServiceDate = "20250218".parse().unwrap();
+        let sunday_schedule = setup_new_schedule(Some((sunday, sunday))).unwrap();
+        let tuesday_schedule = setup_new_schedule(Some((tuesday, tuesday))).unwrap();
+
+        // MTA trip IDs include the day type of their service
+        assert!(
+            sunday_schedule
+                .trips
+                .keys()
+                .all(|id| !id.contains("Weekday"))
+        );
+        assert!(
+            tuesday_schedule
+                .trips
+                .keys()
+                .all(|id| !id.contains("Sunday"))
+        );
+        let (sunday_trips, sunday_stop_times) = raw_counts("20250216");
+        let (tuesday_trips, tuesday_stop_times) = raw_counts("20250218");
+        assert!(0 < sunday_trips && sunday_trips < tuesday_trips);
+        assert_eq!(sunday_schedule.trips.len(), sunday_trips);
+        assert_eq!(tuesday_schedule.trips.len(), tuesday_trips);
+        assert_eq!(
+            sunday_schedule
+                .stop_times
+                .values()
+                .map(Vec::len)
+                .sum::<usize>(),
+            sunday_stop_times
+        );
+        assert_eq!(
+            tuesday_schedule
+                .stop_times
+                .values()
+                .map(Vec::len)
+                .sum::<usize>(),
+            tuesday_stop_times
+        );
+
+        for trip in sunday_schedule.trips.values() {
+            assert!(sunday_schedule.is_service_active(&trip.service_id, sunday));
+        }
+        for trip in tuesday_schedule.trips.values() {
+            assert!(tuesday_schedule.is_service_active(&trip.service_id, tuesday));
         }
     }
 }
@@ -12,6 +12,14 @@ pub struct Agency {
     pub agency_email: Option<String>,
 }
 
+// The feed's only agency, which records may refer to by leaving agency_id empty
+pub(crate) fn sole_agency(agencies: &[Agency]) -> Option<&Agency> {
+    match agencies {
+        [agency] => Some(agency),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
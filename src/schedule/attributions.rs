@@ -0,0 +1,148 @@
+use serde::Deserialize;
+
+use super::Schedule;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Attribution {
+    pub attribution_id: Option<String>,
+    // At most one of these is set, an attribution without any applies to the whole feed
+    pub agency_id: Option<String>,
+    pub route_id: Option<String>,
+    pub trip_id: Option<String>,
+    pub organization_name: String,
+    // Roles are 1 when the organization has them, at least one should be
+    pub is_producer: Option<u32>,
+    pub is_operator: Option<u32>,
+    pub is_authority: Option<u32>,
+    pub attribution_url: Option<String>,
+    pub attribution_email: Option<String>,
+    pub attribution_phone: Option<String>,
+}
+
+impl Attribution {
+    pub fn is_producer(&self) -> bool {
+        self.is_producer == Some(1)
+    }
+
+    pub fn is_operator(&self) -> bool {
+        self.is_operator == Some(1)
+    }
+
+    pub fn is_authority(&self) -> bool {
+        self.is_authority == Some(1)
+    }
+
+    pub fn is_feed_wide(&self) -> bool {
+        self.agency_id.is_none() && self.route_id.is_none() && self.trip_id.is_none()
+    }
+}
+
+impl Schedule {
+    fn matching_attributions(
+        &self,
+        agency_id: Option<&str>,
+        route_id: Option<&str>,
+        trip_id: Option<&str>,
+    ) -> Vec<&Attribution> {
+        self.attributions
+            .iter()
+            .filter(|a| {
+                a.is_feed_wide()
+                    || (a.agency_id.is_some() && a.agency_id.as_deref() == agency_id)
+                    || (a.route_id.is_some() && a.route_id.as_deref() == route_id)
+                    || (a.trip_id.is_some() && a.trip_id.as_deref() == trip_id)
+            })
+            .collect()
+    }
+
+    /// Attributions for an agency, including feed-wide ones, in file order
+    pub fn agency_attributions(&self, agency_id: &str) -> Vec<&Attribution> {
+        self.matching_attributions(Some(agency_id), None, None)
+    }
+
+    /// Attributions for a route, its agency and the whole feed, in file order
+    pub fn route_attributions(&self, route_id: &str) -> Vec<&Attribution> {
+        self.matching_attributions(self.route_agency_id(route_id), Some(route_id), None)
+    }
+
+    /// Attributions for a trip, its route, its agency and the whole feed, in file order
+    pub fn trip_attributions(&self, trip_id: &str) -> Vec<&Attribution> {
+        let route_id = self.trips.get(trip_id).map(|t| t.route_id.as_str());
+
+        self.matching_attributions(
+            route_id.and_then(|id| self.route_agency_id(id)),
+            route_id,
+            Some(trip_id),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::ScheduleLoader;
+
+    fn schedule() -> Schedule {
+        let agencies = "agency_id,agency_name,agency_url,agency_timezone\nMTA NYCT,MTA New York City Transit,http://www.mta.info,America/New_York\nMTABC,MTA Bus Company,http://www.mta.info,America/New_York\n";
+        let routes = "route_id,agency_id,route_type\n1,MTA NYCT,1\nQ10,MTABC,3\n";
+        let services = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250101,20251231\n";
+        let trips = "route_id,trip_id,service_id\n1,a,Weekday\nQ10,b,Weekday\n";
+        let attributions = "attribution_id,agency_id,route_id,trip_id,organization_name,is_producer,is_operator,is_authority,attribution_url\n\
+            feed,,,,Metropolitan Transportation Authority,1,,1,http://www.mta.info\n\
+            nyct,MTA NYCT,,,New York City Transit,,1,,\n\
+            q10,,Q10,,MTA Bus Company,,1,,\n\
+            b,,,b,Trip planner data,1,,,\n";
+
+        ScheduleLoader::new()
+            .file("agency.txt", agencies.as_bytes())
+            .file("routes.txt", routes.as_bytes())
+            .file("calendar.txt", services.as_bytes())
+            .file("trips.txt", trips.as_bytes())
+            .file("attributions.txt", attributions.as_bytes())
+            .skip("stops.txt")
+            .skip("stop_times.txt")
+            .load()
+            .unwrap()
+    }
+
+    fn ids(attributions: Vec<&Attribution>) -> Vec<&str> {
+        attributions
+            .iter()
+            .filter_map(|a| a.attribution_id.as_deref())
+            .collect()
+    }
+
+    #[test]
+    fn test_attribution_roles() -> Result<(), csv::Error> {
+        let schedule = schedule();
+
+        let feed = &schedule.attributions[0];
+        assert!(feed.is_feed_wide());
+        assert!(feed.is_producer() && feed.is_authority() && !feed.is_operator());
+        assert_eq!(feed.attribution_url, Some("http://www.mta.info".to_owned()));
+        assert!(!schedule.attributions[1].is_feed_wide());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_attribution_lookup() -> Result<(), csv::Error> {
+        let schedule = schedule();
+
+        assert_eq!(
+            ids(schedule.agency_attributions("MTA NYCT")),
+            vec!["feed", "nyct"]
+        );
+        assert_eq!(ids(schedule.agency_attributions("MTABC")), vec!["feed"]);
+        assert_eq!(ids(schedule.route_attributions("1")), vec!["feed", "nyct"]);
+        assert_eq!(ids(schedule.route_attributions("Q10")), vec!["feed", "q10"]);
+        assert_eq!(ids(schedule.trip_attributions("a")), vec!["feed", "nyct"]);
+        assert_eq!(
+            ids(schedule.trip_attributions("b")),
+            vec!["feed", "q10", "b"]
+        );
+        assert_eq!(ids(schedule.trip_attributions("c")), vec!["feed"]);
+
+        Ok(())
+    }
+}
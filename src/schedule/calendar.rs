@@ -1,10 +1,18 @@
+use std::collections::{HashMap, HashSet};
+
 use serde::Deserialize;
 
+use super::{
+    Schedule,
+    date::{ServiceDate, Weekday},
+};
+
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
 #[serde(from = "u32")]
 pub enum Activity {
     Inactive,
     Active,
+    Unknown(u32),
 }
 
 impl From<u32> for Activity {
@@ -12,11 +20,22 @@ impl From<u32> for Activity {
         match value {
             0 => Self::Inactive,
             1 => Self::Active,
-            _ => panic!("Invalid Availability: {}", value),
+            _ => Self::Unknown(value),
         }
     }
 }
 
+impl From<Activity> for u32 {
+    fn from(value: Activity) -> Self {
+        match value {
+            Activity::Inactive => 0,
+            Activity::Active => 1,
+            Activity::Unknown(value) => value,
+        }
+    }
+}
+
+// Only a known active day counts
 impl From<Activity> for bool {
     fn from(value: Activity) -> Self {
         value == Activity::Active
@@ -33,8 +52,27 @@ pub struct Service {
     pub thursday: Activity,
     pub friday: Activity,
     pub saturday: Activity,
-    pub start_date: String,
-    pub end_date: String,
+    pub start_date: ServiceDate,
+    pub end_date: ServiceDate,
+}
+
+impl Service {
+    pub fn activity(&self, weekday: Weekday) -> Activity {
+        match weekday {
+            Weekday::Monday => self.monday,
+            Weekday::Tuesday => self.tuesday,
+            Weekday::Wednesday => self.wednesday,
+            Weekday::Thursday => self.thursday,
+            Weekday::Friday => self.friday,
+            Weekday::Saturday => self.saturday,
+            Weekday::Sunday => self.sunday,
+        }
+    }
+
+    /// Whether the regular weekly schedule covers `date`, ignoring any exceptions
+    pub fn runs_on(&self, date: ServiceDate) -> bool {
+        date >= self.start_date && date <= self.end_date && self.activity(date.weekday()).into()
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
@@ -42,6 +80,7 @@ pub struct Service {
 pub enum ExceptionType {
     Added,
     Removed,
+    Unknown(u32),
 }
 
 impl From<u32> for ExceptionType {
@@ -49,7 +88,17 @@ impl From<u32> for ExceptionType {
         match value {
             1 => Self::Added,
             2 => Self::Removed,
-            _ => panic!("Invalid ExceptionType: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<ExceptionType> for u32 {
+    fn from(value: ExceptionType) -> Self {
+        match value {
+            ExceptionType::Added => 1,
+            ExceptionType::Removed => 2,
+            ExceptionType::Unknown(value) => value,
         }
     }
 }
@@ -57,10 +106,71 @@ impl From<u32> for ExceptionType {
 #[derive(Debug, Deserialize, Clone)]
 pub struct ServiceException {
     pub service_id: String,
-    pub date: String,
+    pub date: ServiceDate,
     pub exception_type: ExceptionType,
 }
 
+/// Whether a service operates on `date`. Exceptions from `calendar_dates.txt` take priority over the
+/// weekly schedule in `calendar.txt`, so this also handles services only defined there
+pub fn is_service_active(
+    services: &HashMap<String, Service>,
+    service_exceptions: &HashMap<String, HashMap<ServiceDate, ServiceException>>,
+    service_id: &str,
+    date: ServiceDate,
+) -> bool {
+    let exception = service_exceptions
+        .get(service_id)
+        .and_then(|e| e.get(&date));
+
+    match exception.map(|e| &e.exception_type) {
+        Some(ExceptionType::Added) => true,
+        Some(ExceptionType::Removed) => false,
+        _ => services.get(service_id).is_some_and(|s| s.runs_on(date)),
+    }
+}
+
+impl Schedule {
+    pub fn is_service_active(&self, service_id: &str, date: ServiceDate) -> bool {
+        is_service_active(&self.services, &self.service_exceptions, service_id, date)
+    }
+
+    /// Every service_id that operates on `date`
+    pub fn active_services(&self, date: ServiceDate) -> HashSet<&str> {
+        self.services
+            .keys()
+            .chain(self.service_exceptions.keys())
+            .map(String::as_str)
+            .filter(|id| self.is_service_active(id, date))
+            .collect()
+    }
+
+    /// Every date a service operates on, in order
+    pub fn service_dates(&self, service_id: &str) -> Vec<ServiceDate> {
+        let mut dates: Vec<ServiceDate> = match self.services.get(service_id) {
+            Some(service) => service
+                .start_date
+                .iter_to(service.end_date)
+                .filter(|d| service.runs_on(*d))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        if let Some(exceptions) = self.service_exceptions.get(service_id) {
+            for (date, exception) in exceptions {
+                match exception.exception_type {
+                    ExceptionType::Added => dates.push(*date),
+                    ExceptionType::Removed => dates.retain(|d| d != date),
+                    ExceptionType::Unknown(_) => {}
+                }
+            }
+        }
+
+        dates.sort();
+        dates.dedup();
+        dates
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -89,8 +199,8 @@ mod tests {
         assert_eq!(mta.thursday, Activity::Active);
         assert_eq!(mta.friday, Activity::Active);
         assert_eq!(mta.saturday, Activity::Inactive);
-        assert_eq!(mta.start_date, "20241216");
-        assert_eq!(mta.end_date, "20250606");
+        assert_eq!(mta.start_date.to_string(), "20241216");
+        assert_eq!(mta.end_date.to_string(), "20250606");
 
         Ok(())
     }
@@ -110,9 +220,86 @@ mod tests {
         let mta = res.pop().unwrap();
 
         assert_eq!(mta.service_id, "SIR-FA2017-SI017-Weekday-08_C17");
-        assert_eq!(mta.date, "20250526");
+        assert_eq!(mta.date.to_string(), "20250526");
         assert_eq!(mta.exception_type, ExceptionType::Removed);
 
         Ok(())
     }
+
+    #[test]
+    fn test_service_resolution() -> Result<(), csv::Error> {
+        let services = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250210,20250221\nWeekend,0,0,0,0,0,1,1,20250210,20250221\n";
+        let exceptions = "service_id,date,exception_type\nWeekday,20250217,2\nWeekend,20250217,1\nExtra,20250214,1\nExtra,20250301,1\n";
+
+        let mut schedule = Schedule::default();
+        for rec in csv::Reader::from_reader(services.as_bytes()).deserialize() {
+            let rec: Service = rec?;
+            schedule.services.insert(rec.service_id.clone(), rec);
+        }
+        for rec in csv::Reader::from_reader(exceptions.as_bytes()).deserialize() {
+            let rec: ServiceException = rec?;
+            schedule
+                .service_exceptions
+                .entry(rec.service_id.clone())
+                .or_default()
+                .insert(rec.date, rec);
+        }
+
+        let date = |s: &str| s.parse::<ServiceDate>().unwrap();
+
+        // Presidents' Day is a Monday, run on a weekend schedule
+        assert_eq!(
+            schedule.active_services(date("20250217")),
+            HashSet::from(["Weekend"])
+        );
+        assert_eq!(
+            schedule.active_services(date("20250214")),
+            HashSet::from(["Weekday", "Extra"])
+        );
+        assert_eq!(
+            schedule.active_services(date("20250216")),
+            HashSet::from(["Weekend"])
+        );
+        assert!(schedule.active_services(date("20250222")).is_empty());
+
+        let weekday_dates: Vec<String> = schedule
+            .service_dates("Weekday")
+            .iter()
+            .map(ServiceDate::to_string)
+            .collect();
+        assert_eq!(
+            weekday_dates,
+            vec![
+                "20250210", "20250211", "20250212", "20250213", "20250214", "20250218", "20250219",
+                "20250220", "20250221"
+            ]
+        );
+        assert_eq!(
+            schedule.service_dates("Weekend"),
+            vec![date("20250215"), date("20250216"), date("20250217")]
+        );
+        assert_eq!(
+            schedule.service_dates("Extra"),
+            vec![date("20250214"), date("20250301")]
+        );
+        assert!(schedule.service_dates("Missing").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_activity_codes() -> Result<(), csv::Error> {
+        let data = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,2,0,0,20250210,20250221\n";
+        let service: Service = csv::Reader::from_reader(data.as_bytes())
+            .deserialize()
+            .next()
+            .unwrap()?;
+
+        assert_eq!(service.friday, Activity::Unknown(2));
+        assert_eq!(u32::from(service.friday), 2);
+        assert!(service.runs_on("20250210".parse().unwrap()));
+        assert!(!service.runs_on("20250214".parse().unwrap()));
+
+        Ok(())
+    }
 }
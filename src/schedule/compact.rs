@@ -0,0 +1,579 @@
+use std::{collections::HashMap, mem::size_of};
+
+use super::{
+    Schedule,
+    agency::Agency,
+    calendar::{self, Service, ServiceException},
+    date::ServiceDate,
+    routes::Route,
+    shapes::Shape,
+    stop_times::{DropoffType, PickupType, StopTime, Timepoint},
+    stops::Stop,
+    time::GtfsTime,
+    transfers::Transfer,
+    trips::{BikeSupport, DirectionType, Trip, WheelchairAccessibility},
+};
+
+// Marks an empty stop_id, arrival_time or departure_time in the stop time columns
+const NONE: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Default)]
+/// Maps strings to dense indices, handed out in insertion order
+pub struct Interner {
+    strings: Vec<Box<str>>,
+    indices: HashMap<Box<str>, u32>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, s: &str) -> u32 {
+        if let Some(&idx) = self.indices.get(s) {
+            return idx;
+        }
+
+        let idx = self.strings.len() as u32;
+        self.strings.push(s.into());
+        self.indices.insert(s.into(), idx);
+        idx
+    }
+
+    pub fn get(&self, s: &str) -> Option<u32> {
+        self.indices.get(s).copied()
+    }
+
+    pub fn resolve(&self, idx: u32) -> &str {
+        &self.strings[idx as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    // Approximate, hash map control bytes are counted as one byte per bucket
+    fn heap_size(&self) -> usize {
+        let text: usize = self.strings.iter().map(|s| s.len()).sum();
+
+        self.strings.capacity() * size_of::<Box<str>>()
+            + self.indices.capacity() * (size_of::<(Box<str>, u32)>() + 1)
+            + text * 2
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A trip with its IDs replaced by indices into the `CompactSchedule` interners
+pub struct CompactTrip {
+    pub route: u32,
+    pub service: u32,
+    // Indices into `CompactSchedule::strings`
+    pub trip_headsign: Option<u32>,
+    pub trip_short_name: Option<u32>,
+    pub block_id: Option<u32>,
+    pub shape_id: Option<u32>,
+    pub direction_id: Option<DirectionType>,
+    pub wheelchair_accessible: Option<WheelchairAccessibility>,
+    pub bikes_allowed: Option<BikeSupport>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// The optional stop time fields most feeds leave empty. Only stored for rows that set at least
+/// one of them
+pub struct StopTimeExtras {
+    pub location_group_id: Option<String>,
+    pub location_id: Option<String>,
+    pub start_pickup_drop_off_window: Option<GtfsTime>,
+    pub end_pickup_drop_off_window: Option<GtfsTime>,
+    pub pickup_type: Option<PickupType>,
+    pub drop_off_type: Option<DropoffType>,
+    pub continuous_pickup: Option<PickupType>,
+    pub continuous_drop_off: Option<DropoffType>,
+    pub shape_dist_traveled: Option<f64>,
+    pub timepoint: Option<Timepoint>,
+    pub pickup_booking_rule_id: Option<String>,
+    pub drop_off_booking_rule_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// A read-only `Schedule` that interns stop, trip, route and service IDs into dense `u32`
+/// indices and stores stop times in columns. Only the core timetable is kept, use `Schedule` for
+/// fares, pathways, translations and the other optional files.
+///
+/// A `StopTime` is 264 bytes before its heap allocated `trip_id` and `stop_id`. Here a stop time
+/// takes 16 bytes (stop, stop_sequence, arrival and departure columns), plus an entry in a sparse
+/// map only when it has a headsign or one of the `StopTimeExtras`. For the first 10,000 stop
+/// times of the MTA subway feed that comes to about 21 bytes a stop time instead of 490. The
+/// ignored `test_compact_full` checks the full feed in `test_data/schedule` (2,339,542 stop times)
+/// stays at least ten times smaller, run it with `cargo test -- --ignored` when the feed is there
+pub struct CompactSchedule {
+    pub agencies: Vec<Agency>,
+    pub stop_ids: Interner,
+    pub trip_ids: Interner,
+    pub route_ids: Interner,
+    pub service_ids: Interner,
+    // Headsigns, shape IDs and other text repeated across many records
+    pub strings: Interner,
+    // Indexed by stop index, IDs referenced by stop times but missing from stops.txt have none
+    stops: Vec<Stop>,
+    // Indexed by route index, same as stops
+    routes: Vec<Route>,
+    // Indexed by trip index
+    trips: Vec<CompactTrip>,
+    // Indexed by service_id
+    pub services: HashMap<String, Service>,
+    // Indexed by service_id, then date
+    pub service_exceptions: HashMap<String, HashMap<ServiceDate, ServiceException>>,
+    // Indexed by shape_id
+    pub shapes: HashMap<String, Shape>,
+    // Indexed by from_stop_id
+    pub transfers: HashMap<String, Vec<Transfer>>,
+    // Rows of trip i are stop_time_offsets[i]..stop_time_offsets[i + 1], ordered by stop_sequence
+    stop_time_offsets: Vec<u32>,
+    stop_time_stops: Vec<u32>,
+    stop_time_sequences: Vec<u32>,
+    arrival_times: Vec<u32>,
+    departure_times: Vec<u32>,
+    // Indexed by row, only rows that have them
+    stop_headsigns: HashMap<u32, u32>,
+    stop_time_extras: HashMap<u32, StopTimeExtras>,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A single stop time of a `CompactSchedule`
+pub struct CompactStopTime<'a> {
+    schedule: &'a CompactSchedule,
+    trip: u32,
+    row: u32,
+}
+
+impl<'a> CompactStopTime<'a> {
+    pub fn trip(&self) -> u32 {
+        self.trip
+    }
+
+    pub fn trip_id(&self) -> &'a str {
+        self.schedule.trip_ids.resolve(self.trip)
+    }
+
+    pub fn stop(&self) -> Option<u32> {
+        Some(self.schedule.stop_time_stops[self.row as usize]).filter(|&idx| idx != NONE)
+    }
+
+    pub fn stop_id(&self) -> Option<&'a str> {
+        self.stop().map(|idx| self.schedule.stop_ids.resolve(idx))
+    }
+
+    pub fn stop_sequence(&self) -> u32 {
+        self.schedule.stop_time_sequences[self.row as usize]
+    }
+
+    pub fn arrival_time(&self) -> Option<GtfsTime> {
+        Some(self.schedule.arrival_times[self.row as usize])
+            .filter(|&secs| secs != NONE)
+            .map(GtfsTime::from_secs)
+    }
+
+    pub fn departure_time(&self) -> Option<GtfsTime> {
+        Some(self.schedule.departure_times[self.row as usize])
+            .filter(|&secs| secs != NONE)
+            .map(GtfsTime::from_secs)
+    }
+
+    pub fn stop_headsign(&self) -> Option<&'a str> {
+        let idx = self.schedule.stop_headsigns.get(&self.row)?;
+        Some(self.schedule.strings.resolve(*idx))
+    }
+
+    pub fn extras(&self) -> Option<&'a StopTimeExtras> {
+        self.schedule.stop_time_extras.get(&self.row)
+    }
+
+    /// Rebuilds the full `StopTime`
+    pub fn to_stop_time(&self) -> StopTime {
+        let extras = self.extras().cloned().unwrap_or_default();
+
+        StopTime {
+            trip_id: self.trip_id().to_owned(),
+            arrival_time: self.arrival_time(),
+            departure_time: self.departure_time(),
+            stop_id: self.stop_id().map(str::to_owned),
+            location_group_id: extras.location_group_id,
+            location_id: extras.location_id,
+            stop_sequence: self.stop_sequence(),
+            stop_headsign: self.stop_headsign().map(str::to_owned),
+            start_pickup_drop_off_window: extras.start_pickup_drop_off_window,
+            end_pickup_drop_off_window: extras.end_pickup_drop_off_window,
+            pickup_type: extras.pickup_type,
+            drop_off_type: extras.drop_off_type,
+            continuous_pickup: extras.continuous_pickup,
+            continuous_drop_off: extras.continuous_drop_off,
+            shape_dist_traveled: extras.shape_dist_traveled,
+            timepoint: extras.timepoint,
+            pickup_booking_rule_id: extras.pickup_booking_rule_id,
+            drop_off_booking_rule_id: extras.drop_off_booking_rule_id,
+        }
+    }
+}
+
+impl CompactSchedule {
+    pub fn stop(&self, idx: u32) -> Option<&Stop> {
+        self.stops.get(idx as usize)
+    }
+
+    pub fn route(&self, idx: u32) -> Option<&Route> {
+        self.routes.get(idx as usize)
+    }
+
+    pub fn trip(&self, idx: u32) -> Option<&CompactTrip> {
+        self.trips.get(idx as usize)
+    }
+
+    pub fn trip_count(&self) -> usize {
+        self.trips.len()
+    }
+
+    pub fn stop_time_count(&self) -> usize {
+        self.stop_time_stops.len()
+    }
+
+    /// Stop times of a trip ordered by stop_sequence, empty for unknown trips
+    pub fn trip_stops(&self, trip: u32) -> impl ExactSizeIterator<Item = CompactStopTime<'_>> {
+        let rows = match (
+            self.stop_time_offsets.get(trip as usize),
+            self.stop_time_offsets.get(trip as usize + 1),
+        ) {
+            (Some(&start), Some(&end)) => start..end,
+            _ => 0..0,
+        };
+
+        rows.map(move |row| CompactStopTime {
+            schedule: self,
+            trip,
+            row,
+        })
+    }
+
+    pub fn is_trip_active(&self, trip: u32, date: ServiceDate) -> bool {
+        self.trip(trip).is_some_and(|t| {
+            calendar::is_service_active(
+                &self.services,
+                &self.service_exceptions,
+                self.service_ids.resolve(t.service),
+                date,
+            )
+        })
+    }
+
+    /// Approximate heap memory used by stop times, including the trip and stop interners
+    pub fn stop_times_heap_size(&self) -> usize {
+        let columns = self.stop_time_offsets.capacity()
+            + self.stop_time_stops.capacity()
+            + self.stop_time_sequences.capacity()
+            + self.arrival_times.capacity()
+            + self.departure_times.capacity();
+        let extras_text: usize = self
+            .stop_time_extras
+            .values()
+            .flat_map(|e| {
+                [
+                    &e.location_group_id,
+                    &e.location_id,
+                    &e.pickup_booking_rule_id,
+                    &e.drop_off_booking_rule_id,
+                ]
+            })
+            .flatten()
+            .map(String::capacity)
+            .sum();
+
+        columns * size_of::<u32>()
+            + self.stop_headsigns.capacity() * (size_of::<(u32, u32)>() + 1)
+            + self.stop_time_extras.capacity() * (size_of::<(u32, StopTimeExtras)>() + 1)
+            + extras_text
+            + self.trip_ids.heap_size()
+            + self.stop_ids.heap_size()
+    }
+
+    fn push_stop_time(&mut self, st: StopTime) {
+        let row = self.stop_time_stops.len() as u32;
+
+        let stop = st.stop_id.as_deref().map(|id| self.stop_ids.intern(id));
+        self.stop_time_stops.push(stop.unwrap_or(NONE));
+        self.stop_time_sequences.push(st.stop_sequence);
+        self.arrival_times
+            .push(st.arrival_time.map_or(NONE, GtfsTime::secs));
+        self.departure_times
+            .push(st.departure_time.map_or(NONE, GtfsTime::secs));
+
+        if let Some(headsign) = st.stop_headsign {
+            let idx = self.strings.intern(&headsign);
+            self.stop_headsigns.insert(row, idx);
+        }
+
+        let extras = StopTimeExtras {
+            location_group_id: st.location_group_id,
+            location_id: st.location_id,
+            start_pickup_drop_off_window: st.start_pickup_drop_off_window,
+            end_pickup_drop_off_window: st.end_pickup_drop_off_window,
+            pickup_type: st.pickup_type,
+            drop_off_type: st.drop_off_type,
+            continuous_pickup: st.continuous_pickup,
+            continuous_drop_off: st.continuous_drop_off,
+            shape_dist_traveled: st.shape_dist_traveled,
+            timepoint: st.timepoint,
+            pickup_booking_rule_id: st.pickup_booking_rule_id,
+            drop_off_booking_rule_id: st.drop_off_booking_rule_id,
+        };
+        if extras != StopTimeExtras::default() {
+            self.stop_time_extras.insert(row, extras);
+        }
+    }
+}
+
+impl From<Schedule> for CompactSchedule {
+    /// Consumes the schedule so its stop times can be freed as they're converted. Stops, routes
+    /// and trips are indexed in order of their IDs
+    fn from(schedule: Schedule) -> Self {
+        let Schedule {
+            agencies,
+            stops,
+            routes,
+            trips,
+            mut stop_times,
+            services,
+            service_exceptions,
+            shapes,
+            transfers,
+            ..
+        } = schedule;
+
+        let mut compact = Self {
+            agencies,
+            services,
+            service_exceptions,
+            shapes,
+            transfers,
+            ..Default::default()
+        };
+
+        let mut stops: Vec<Stop> = stops.into_values().collect();
+        stops.sort_by(|a, b| a.stop_id.cmp(&b.stop_id));
+        for stop in &stops {
+            compact.stop_ids.intern(&stop.stop_id);
+        }
+        compact.stops = stops;
+
+        let mut routes: Vec<Route> = routes.into_values().collect();
+        routes.sort_by(|a, b| a.route_id.cmp(&b.route_id));
+        for route in &routes {
+            compact.route_ids.intern(&route.route_id);
+        }
+        compact.routes = routes;
+
+        let mut trips: Vec<Trip> = trips.into_values().collect();
+        trips.sort_by(|a, b| a.trip_id.cmp(&b.trip_id));
+        compact.stop_time_offsets.push(0);
+        for trip in trips {
+            compact.trip_ids.intern(&trip.trip_id);
+            let mut intern = |s: Option<String>| s.map(|s| compact.strings.intern(&s));
+            let compact_trip = CompactTrip {
+                trip_headsign: intern(trip.trip_headsign),
+                trip_short_name: intern(trip.trip_short_name),
+                block_id: intern(trip.block_id),
+                shape_id: intern(trip.shape_id),
+                route: compact.route_ids.intern(&trip.route_id),
+                service: compact.service_ids.intern(&trip.service_id),
+                direction_id: trip.direction_id,
+                wheelchair_accessible: trip.wheelchair_accessible,
+                bikes_allowed: trip.bikes_allowed,
+            };
+            compact.trips.push(compact_trip);
+
+            for st in stop_times.remove(&trip.trip_id).unwrap_or_default() {
+                compact.push_stop_time(st);
+            }
+            compact
+                .stop_time_offsets
+                .push(compact.stop_time_stops.len() as u32);
+        }
+
+        compact.stop_time_stops.shrink_to_fit();
+        compact.stop_time_sequences.shrink_to_fit();
+        compact.arrival_times.shrink_to_fit();
+        compact.departure_times.shrink_to_fit();
+        compact
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::{LoadOptions, ScheduleLoader};
+
+    fn schedule() -> Schedule {
+        let stop_times = "trip_id,arrival_time,departure_time,stop_id,stop_sequence,stop_headsign,pickup_type\n\
+            a,08:00:00,08:00:30,101,1,Downtown,\n\
+            a,08:05:00,08:05:00,103,2,,\n\
+            a,,,104,3,,1\n\
+            b,09:00:00,09:00:00,103,1,,\n\
+            b,09:10:00,09:10:00,999,2,Downtown,\n";
+
+        ScheduleLoader::new()
+            .file(
+                "agency.txt",
+                "agency_name,agency_url,agency_timezone\nMTA,http://www.mta.info,America/New_York\n"
+                    .as_bytes(),
+            )
+            .file(
+                "stops.txt",
+                "stop_id,stop_name\n101,Van Cortlandt Park-242 St\n103,238 St\n104,231 St\n"
+                    .as_bytes(),
+            )
+            .file("calendar.txt", "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250101,20251231\n".as_bytes())
+            .file("routes.txt", "route_id,route_type\n1,1\n".as_bytes())
+            .file("trips.txt", "route_id,trip_id,service_id,trip_headsign\n1,a,Weekday,South Ferry\n1,b,Weekday,South Ferry\n".as_bytes())
+            .file("stop_times.txt", stop_times.as_bytes())
+            .load()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_compact() {
+        let schedule = schedule();
+        let compact = CompactSchedule::from(schedule.clone());
+
+        assert_eq!(compact.trip_count(), 2);
+        assert_eq!(compact.stop_time_count(), 5);
+        // Stop 999 isn't in stops.txt but still gets an index
+        assert_eq!(compact.stop_ids.len(), 4);
+
+        let a = compact.trip_ids.get("a").unwrap();
+        let trip = compact.trip(a).unwrap();
+        assert_eq!(compact.route_ids.resolve(trip.route), "1");
+        assert_eq!(
+            trip.trip_headsign.map(|idx| compact.strings.resolve(idx)),
+            Some("South Ferry")
+        );
+
+        let stops: Vec<CompactStopTime> = compact.trip_stops(a).collect();
+        assert_eq!(stops.len(), 3);
+        assert_eq!(stops[0].stop_id(), Some("101"));
+        assert_eq!(
+            stops[0].departure_time(),
+            Some(GtfsTime::from_hms(8, 0, 30))
+        );
+        assert_eq!(stops[0].stop_headsign(), Some("Downtown"));
+        assert!(stops[0].extras().is_none());
+        assert_eq!(stops[2].arrival_time(), None);
+        assert_eq!(
+            stops[2].extras().and_then(|e| e.pickup_type.clone()),
+            Some(PickupType::NoPickup)
+        );
+
+        let b = compact.trip_ids.get("b").unwrap();
+        let stops: Vec<CompactStopTime> = compact.trip_stops(b).collect();
+        assert_eq!(stops[1].stop_id(), Some("999"));
+        assert!(compact.stop(stops[1].stop().unwrap()).is_none());
+        assert_eq!(compact.trip_stops(7).count(), 0);
+
+        // Nothing is lost converting back
+        for trip_id in ["a", "b"] {
+            let idx = compact.trip_ids.get(trip_id).unwrap();
+            let rebuilt: Vec<StopTime> = compact
+                .trip_stops(idx)
+                .map(|st| st.to_stop_time())
+                .collect();
+            let original = schedule.trip_stops(trip_id);
+            assert_eq!(rebuilt.len(), original.len());
+            for (rebuilt, original) in rebuilt.iter().zip(original) {
+                assert_eq!(rebuilt.stop_id, original.stop_id);
+                assert_eq!(rebuilt.stop_sequence, original.stop_sequence);
+                assert_eq!(rebuilt.arrival_time, original.arrival_time);
+                assert_eq!(rebuilt.stop_headsign, original.stop_headsign);
+                assert_eq!(rebuilt.pickup_type, original.pickup_type);
+            }
+        }
+
+        let monday = "20250106".parse().unwrap();
+        let sunday = "20250105".parse().unwrap();
+        assert!(compact.is_trip_active(a, monday));
+        assert!(!compact.is_trip_active(a, sunday));
+    }
+
+    // Heap used by the stop times of a `Schedule`, counted the same way as
+    // `CompactSchedule::stop_times_heap_size`
+    fn stop_times_heap_size(schedule: &Schedule) -> usize {
+        let strings = |st: &StopTime| -> usize {
+            [
+                Some(&st.trip_id),
+                st.stop_id.as_ref(),
+                st.location_group_id.as_ref(),
+                st.location_id.as_ref(),
+                st.stop_headsign.as_ref(),
+                st.pickup_booking_rule_id.as_ref(),
+                st.drop_off_booking_rule_id.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .map(String::capacity)
+            .sum()
+        };
+
+        schedule.stop_times.capacity() * (size_of::<(String, Vec<StopTime>)>() + 1)
+            + schedule
+                .stop_times
+                .iter()
+                .map(|(trip_id, stop_times)| {
+                    trip_id.capacity()
+                        + stop_times.capacity() * size_of::<StopTime>()
+                        + stop_times.iter().map(strings).sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+
+    #[test]
+    #[ignore]
+    fn test_compact_full() {
+        let schedule = Schedule::from_dir("./test_data/schedule", LoadOptions::default()).unwrap();
+        let before = stop_times_heap_size(&schedule);
+        let compact = CompactSchedule::from(schedule);
+        let after = compact.stop_times_heap_size();
+
+        assert_eq!(compact.stop_time_count(), 2_339_542);
+        assert!(after * 10 < before, "{} bytes vs {} bytes", after, before);
+    }
+
+    // The first 10,000 stop times of the MTA subway feed, with a trip made up for each trip_id
+    #[test]
+    fn test_compact_abbrev() {
+        let stop_times =
+            std::fs::read_to_string("./test_data/schedule/stop_times_abbrev.txt").unwrap();
+        let mut trips = String::from("route_id,trip_id,service_id\n");
+        let mut trip_ids: Vec<&str> = stop_times
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split(',').next())
+            .collect();
+        trip_ids.dedup();
+        for trip_id in trip_ids {
+            trips.push_str(&format!("1,{},Daily\n", trip_id));
+        }
+
+        let schedule = ScheduleLoader::new()
+            .file("calendar.txt", "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nDaily,1,1,1,1,1,1,1,20250101,20251231\n".as_bytes())
+            .file("routes.txt", "route_id,route_type\n1,1\n".as_bytes())
+            .file("trips.txt", trips.as_bytes())
+            .file("stop_times.txt", stop_times.as_bytes())
+            .skip("agency.txt")
+            .skip("stops.txt")
+            .load()
+            .unwrap();
+        let before = stop_times_heap_size(&schedule);
+        let compact = CompactSchedule::from(schedule);
+        let after = compact.stop_times_heap_size();
+
+        assert_eq!(compact.stop_time_count(), 10_000);
+        assert!(after * 10 < before, "{} bytes vs {} bytes", after, before);
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    fmt,
+    iter::FusedIterator,
+    ops::{Add, AddAssign, Sub, SubAssign},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize, de::Visitor};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A validated service date, written as `YYYYMMDD` in GTFS files. Stored as the number of days
+/// since 1970-01-01 so that arithmetic and ordering are cheap
+pub struct ServiceDate(i32);
+
+// Conversions between days since the epoch and the proleptic Gregorian calendar, based on Howard
+// Hinnant's `days_from_civil` and `civil_from_days`
+fn days_from_civil(year: i32, month: u32, day: u32) -> i32 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400) as u32;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe as i32 - 719468
+}
+
+fn civil_from_days(days: i32) -> (i32, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097) as u32;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe as i32 + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+impl ServiceDate {
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Self> {
+        if day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        Some(Self(days_from_civil(year, month, day)))
+    }
+
+    pub fn year(self) -> i32 {
+        civil_from_days(self.0).0
+    }
+
+    pub fn month(self) -> u32 {
+        civil_from_days(self.0).1
+    }
+
+    pub fn day(self) -> u32 {
+        civil_from_days(self.0).2
+    }
+
+    pub fn weekday(self) -> Weekday {
+        // 1970-01-01 was a Thursday
+        match self.0.rem_euclid(7) {
+            0 => Weekday::Thursday,
+            1 => Weekday::Friday,
+            2 => Weekday::Saturday,
+            3 => Weekday::Sunday,
+            4 => Weekday::Monday,
+            5 => Weekday::Tuesday,
+            _ => Weekday::Wednesday,
+        }
+    }
+
+    /// Iterates over every date from `self` to `end`, inclusive
+    pub fn iter_to(self, end: Self) -> ServiceDateRange {
+        ServiceDateRange {
+            next: self.0,
+            end: end.0,
+        }
+    }
+}
+
+impl FromStr for ServiceDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid ServiceDate: {}", s);
+
+        let s = s.trim();
+        if s.len() != 8 || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let year: i32 = s[0..4].parse().map_err(|_| invalid())?;
+        let month: u32 = s[4..6].parse().map_err(|_| invalid())?;
+        let day: u32 = s[6..8].parse().map_err(|_| invalid())?;
+
+        Self::from_ymd(year, month, day).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for ServiceDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = civil_from_days(self.0);
+        write!(f, "{:04}{:02}{:02}", year, month, day)
+    }
+}
+
+impl Add<i32> for ServiceDate {
+    type Output = Self;
+
+    fn add(self, rhs: i32) -> Self::Output {
+        Self(self.0 + rhs)
+    }
+}
+
+impl AddAssign<i32> for ServiceDate {
+    fn add_assign(&mut self, rhs: i32) {
+        self.0 += rhs;
+    }
+}
+
+impl Sub<i32> for ServiceDate {
+    type Output = Self;
+
+    fn sub(self, rhs: i32) -> Self::Output {
+        Self(self.0 - rhs)
+    }
+}
+
+impl SubAssign<i32> for ServiceDate {
+    fn sub_assign(&mut self, rhs: i32) {
+        self.0 -= rhs;
+    }
+}
+
+impl Sub for ServiceDate {
+    // Number of days between the two dates
+    type Output = i32;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.0 - rhs.0
+    }
+}
+
+impl<'de> Deserialize<'de> for ServiceDate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct ServiceDateVisitor;
+
+        impl Visitor<'_> for ServiceDateVisitor {
+            type Value = ServiceDate;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a date in YYYYMMDD format")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(ServiceDateVisitor)
+    }
+}
+
+impl Serialize for ServiceDate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceDateRange {
+    next: i32,
+    end: i32,
+}
+
+impl Iterator for ServiceDateRange {
+    type Item = ServiceDate;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next > self.end {
+            return None;
+        }
+
+        self.next += 1;
+        Some(ServiceDate(self.next - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end - self.next + 1).max(0) as usize;
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for ServiceDateRange {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.next > self.end {
+            return None;
+        }
+
+        self.end -= 1;
+        Some(ServiceDate(self.end + 1))
+    }
+}
+
+impl ExactSizeIterator for ServiceDateRange {}
+
+impl FusedIterator for ServiceDateRange {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "20250217".parse(),
+            Ok(ServiceDate::from_ymd(2025, 2, 17).unwrap())
+        );
+        assert_eq!(
+            "20240229".parse(),
+            Ok(ServiceDate::from_ymd(2024, 2, 29).unwrap())
+        );
+
+        for invalid in [
+            "20250231",
+            "20250229",
+            "19000229",
+            "20251301",
+            "20250100",
+            "2025021",
+            "2025-2-17",
+        ] {
+            assert!(invalid.parse::<ServiceDate>().is_err(), "{}", invalid);
+        }
+
+        let date: ServiceDate = "20000229".parse().unwrap();
+        assert_eq!((date.year(), date.month(), date.day()), (2000, 2, 29));
+        assert_eq!(date.to_string(), "20000229");
+    }
+
+    #[test]
+    fn test_weekday() {
+        let date: ServiceDate = "20250217".parse().unwrap();
+        assert_eq!(date.weekday(), Weekday::Monday);
+        assert_eq!((date - 1).weekday(), Weekday::Sunday);
+        assert_eq!((date + 5).weekday(), Weekday::Saturday);
+        assert_eq!(
+            "19691231".parse::<ServiceDate>().unwrap().weekday(),
+            Weekday::Wednesday
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_and_ranges() {
+        let start: ServiceDate = "20241230".parse().unwrap();
+        let end: ServiceDate = "20250302".parse().unwrap();
+
+        assert_eq!(end - start, 62);
+        assert_eq!((start + 2).to_string(), "20250101");
+        assert_eq!((end - 2).to_string(), "20250228");
+
+        let range = start.iter_to(end);
+        assert_eq!(range.len(), 63);
+        assert_eq!(range.clone().next(), Some(start));
+        assert_eq!(range.clone().next_back(), Some(end));
+        assert!(
+            range
+                .collect::<Vec<_>>()
+                .windows(2)
+                .all(|w| w[1] - w[0] == 1)
+        );
+        assert_eq!(end.iter_to(start).count(), 0);
+    }
+}
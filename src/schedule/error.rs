@@ -0,0 +1,185 @@
+use std::{fmt, io};
+
+use csv::ErrorKind;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Error {
+    // A file the spec marks as required was not provided
+    MissingFile(&'static str),
+    // A single record (or header) of a file could not be parsed
+    Parse(ParseError),
+    Io(io::Error),
+    #[cfg(feature = "zip")]
+    Zip(zip::result::ZipError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingFile(file) => write!(f, "Missing required file: {}", file),
+            Self::Parse(e) => e.fmt(f),
+            Self::Io(e) => write!(f, "IO error: {}", e),
+            #[cfg(feature = "zip")]
+            Self::Zip(e) => write!(f, "Zip error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::MissingFile(_) => None,
+            Self::Parse(e) => Some(e),
+            Self::Io(e) => Some(e),
+            #[cfg(feature = "zip")]
+            Self::Zip(e) => Some(e),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(value: ParseError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl Error {
+    // A failed read says nothing about the data, so it stays an IO error rather than becoming a
+    // `ParseError` that lenient mode would skip over
+    pub(crate) fn from_csv(file: &'static str, err: csv::Error) -> Self {
+        if !err.is_io_error() {
+            return ParseError::from_csv(file, err).into();
+        }
+
+        match err.into_kind() {
+            ErrorKind::Io(e) => Self::Io(e),
+            _ => unreachable!("is_io_error only holds for ErrorKind::Io"),
+        }
+    }
+}
+
+#[cfg(feature = "zip")]
+impl From<zip::result::ZipError> for Error {
+    fn from(value: zip::result::ZipError) -> Self {
+        Self::Zip(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    // Fail on the first record that can't be parsed
+    #[default]
+    Strict,
+    // Skip records that can't be parsed, recording a diagnostic for each
+    Lenient,
+}
+
+/// A record that was skipped while parsing in `ParseMode::Lenient`
+pub type ParseDiagnostic = ParseError;
+
+#[derive(Debug, Clone, Default)]
+/// Carries the parse mode through the `parse_*` functions and collects diagnostics for any records
+/// skipped along the way
+pub struct ParseContext {
+    pub mode: ParseMode,
+    pub diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl ParseContext {
+    pub fn new(mode: ParseMode) -> Self {
+        Self {
+            mode,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn strict() -> Self {
+        Self::new(ParseMode::Strict)
+    }
+
+    pub fn lenient() -> Self {
+        Self::new(ParseMode::Lenient)
+    }
+
+    /// Passes a successfully parsed record through. In lenient mode a parse failure is recorded and
+    /// `None` is returned so the caller can move on to the next record, other errors are always
+    /// returned
+    pub(crate) fn accept<T>(&mut self, rec: Result<T>) -> Result<Option<T>> {
+        match (rec, self.mode) {
+            (Ok(rec), _) => Ok(Some(rec)),
+            (Err(Error::Parse(e)), ParseMode::Lenient) => {
+                self.diagnostics.push(e);
+                Ok(None)
+            }
+            (Err(e), _) => Err(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Describes where in a GTFS file parsing failed. Record numbers start at 1 for the first row
+/// after the header, lines are 1-based and include the header
+pub struct ParseError {
+    pub file: &'static str,
+    pub record: Option<u64>,
+    pub line: Option<u64>,
+    // Name of the offending column, from the header row
+    pub column: Option<String>,
+    // Raw text of the offending field
+    pub value: Option<String>,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn from_csv(file: &'static str, err: csv::Error) -> Self {
+        let (record, line) = match err.position() {
+            Some(pos) => (Some(pos.record()), Some(pos.line())),
+            None => (None, None),
+        };
+
+        let message = match err.kind() {
+            ErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => format!("Expected {} fields, found {}", expected_len, len),
+            _ => err.to_string(),
+        };
+
+        Self {
+            file,
+            record,
+            line,
+            column: None,
+            value: None,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, " line {}", line)?;
+        }
+        if let Some(record) = self.record {
+            write!(f, " (record {})", record)?;
+        }
+        if let Some(column) = &self.column {
+            write!(f, ", column `{}`", column)?;
+        }
+        if let Some(value) = &self.value {
+            write!(f, " = {:?}", value)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,500 @@
+use std::{collections::HashSet, ops::Range};
+
+use serde::Deserialize;
+
+use super::{Schedule, time::GtfsTime};
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(from = "u32")]
+pub enum PaymentMethod {
+    OnBoard,
+    BeforeBoarding,
+    Unknown(u32),
+}
+
+impl From<u32> for PaymentMethod {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::OnBoard,
+            1 => Self::BeforeBoarding,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<PaymentMethod> for u32 {
+    fn from(value: PaymentMethod) -> Self {
+        match value {
+            PaymentMethod::OnBoard => 0,
+            PaymentMethod::BeforeBoarding => 1,
+            PaymentMethod::Unknown(value) => value,
+        }
+    }
+}
+
+// An empty transfers field means unlimited transfers, which is represented by `None`
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(from = "u32")]
+pub enum TransferLimit {
+    NoTransfers,
+    OneTransfer,
+    TwoTransfers,
+    Unknown(u32),
+}
+
+impl From<u32> for TransferLimit {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::NoTransfers,
+            1 => Self::OneTransfer,
+            2 => Self::TwoTransfers,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<TransferLimit> for u32 {
+    fn from(value: TransferLimit) -> Self {
+        match value {
+            TransferLimit::NoTransfers => 0,
+            TransferLimit::OneTransfer => 1,
+            TransferLimit::TwoTransfers => 2,
+            TransferLimit::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FareAttribute {
+    pub fare_id: String,
+    pub price: f64,
+    pub currency_type: String,
+    pub payment_method: PaymentMethod,
+    pub transfers: Option<TransferLimit>,
+    pub agency_id: Option<String>,
+    pub transfer_duration: Option<u32>,
+}
+
+impl FareAttribute {
+    /// Number of transfers allowed on this fare, `None` if unlimited
+    pub fn max_transfers(&self) -> Option<usize> {
+        // The codes are the number of transfers, which is the best guess for unknown ones too
+        self.transfers.clone().map(|t| u32::from(t) as usize)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FareRule {
+    pub fare_id: String,
+    pub route_id: Option<String>,
+    pub origin_id: Option<String>,
+    pub destination_id: Option<String>,
+    pub contains_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+/// One leg of a journey, as used for fare calculation. Zones are the `zone_id`s of the boarding and
+/// alighting stops, and are also taken as the zones the leg passes through when checking
+/// `contains_id` rules, since zones of stops passed on the way aren't known
+pub struct FareLeg {
+    pub route_id: String,
+    pub origin_zone: Option<String>,
+    pub destination_zone: Option<String>,
+    // Only needed to enforce `transfer_duration`
+    pub start_time: Option<GtfsTime>,
+}
+
+impl FareLeg {
+    pub fn new(route_id: &str, origin_zone: Option<&str>, destination_zone: Option<&str>) -> Self {
+        Self {
+            route_id: route_id.to_owned(),
+            origin_zone: origin_zone.map(str::to_owned),
+            destination_zone: destination_zone.map(str::to_owned),
+            start_time: None,
+        }
+    }
+
+    /// Builds a leg between two stops, looking up their zones. Returns `None` if either stop
+    /// doesn't exist
+    pub fn from_stops(
+        schedule: &Schedule,
+        route_id: &str,
+        origin_stop_id: &str,
+        destination_stop_id: &str,
+    ) -> Option<Self> {
+        let origin = schedule.stops.get(origin_stop_id)?;
+        let destination = schedule.stops.get(destination_stop_id)?;
+
+        Some(Self::new(
+            route_id,
+            origin.zone_id.as_deref(),
+            destination.zone_id.as_deref(),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A fare covering a run of consecutive legs of a journey
+pub struct FareQuote {
+    pub fare_id: String,
+    pub price: f64,
+    pub currency_type: String,
+    // Indices into the legs passed to `calculate_fares`
+    pub legs: Range<usize>,
+}
+
+// Matches a fare's rules against a run of consecutive legs covered by that fare, one leg at a
+// time. Rows with a contains_id together list the zones the whole run has to pass through, the
+// other rows are matched against each leg on its own
+struct FareMatch<'a> {
+    // `None` for fares without rules, which apply to everything
+    rules: Option<&'a [FareRule]>,
+    contains: HashSet<&'a str>,
+    zones: HashSet<&'a str>,
+    // Whether every leg so far matches a row without a contains_id
+    by_leg: bool,
+    // Whether every leg so far is on a route of the contains rows, within their zones
+    by_zones: bool,
+}
+
+impl<'a> FareMatch<'a> {
+    fn new(schedule: &'a Schedule, fare_id: &str) -> Self {
+        let rules = schedule
+            .fare_rules
+            .get(fare_id)
+            .map(Vec::as_slice)
+            .filter(|r| !r.is_empty());
+        let contains: HashSet<&str> = rules
+            .into_iter()
+            .flatten()
+            .filter_map(|r| r.contains_id.as_deref())
+            .collect();
+
+        Self {
+            rules,
+            by_zones: !contains.is_empty(),
+            contains,
+            zones: HashSet::new(),
+            by_leg: true,
+        }
+    }
+
+    // Adds the next leg of the run, returning false once no longer run can match either
+    fn push(&mut self, leg: &'a FareLeg) -> bool {
+        let Some(rules) = self.rules else {
+            return true;
+        };
+
+        let matches = |rule_value: &Option<String>, leg_value: &Option<String>| {
+            rule_value.is_none() || rule_value == leg_value
+        };
+        let on_route = |r: &FareRule| r.route_id.as_ref().is_none_or(|id| id == &leg.route_id);
+
+        self.by_leg &= rules.iter().any(|r| {
+            r.contains_id.is_none()
+                && on_route(r)
+                && matches(&r.origin_id, &leg.origin_zone)
+                && matches(&r.destination_id, &leg.destination_zone)
+        });
+
+        // Legs with unknown zones can't be shown to stay within the contains zones
+        let zones = [&leg.origin_zone, &leg.destination_zone];
+        self.by_zones &= rules.iter().any(|r| r.contains_id.is_some() && on_route(r))
+            && zones
+                .iter()
+                .all(|z| z.as_deref().is_some_and(|z| self.contains.contains(z)));
+        self.zones
+            .extend(zones.into_iter().filter_map(|z| z.as_deref()));
+
+        self.by_leg || self.by_zones
+    }
+
+    // Whether the fare covers the legs pushed so far
+    fn matches(&self) -> bool {
+        self.rules.is_none() || self.by_leg || (self.by_zones && self.zones == self.contains)
+    }
+}
+
+impl Schedule {
+    /// Every fare that could be used for a single leg
+    pub fn fares_for_leg(&self, leg: &FareLeg) -> Vec<&FareAttribute> {
+        let mut fares: Vec<&FareAttribute> = self
+            .fare_attributes
+            .values()
+            .filter(|f| {
+                let mut fare_match = FareMatch::new(self, &f.fare_id);
+                fare_match.push(leg) && fare_match.matches()
+            })
+            .collect();
+
+        fares.sort_by(|a, b| a.price.total_cmp(&b.price).then(a.fare_id.cmp(&b.fare_id)));
+        fares
+    }
+
+    /// Finds the cheapest combination of fares covering every leg of a journey, using each fare's
+    /// transfer allowance to cover consecutive legs with a single fare where possible. A fare with
+    /// `contains_id` rules covers a run of legs when the zones of the whole run are exactly its
+    /// contains zones. Returns `None` if some leg can't be covered by any fare
+    pub fn calculate_fares(&self, legs: &[FareLeg]) -> Option<Vec<FareQuote>> {
+        // best[i] is the cheapest way to pay for legs[i..], as (total, fare, end of first fare)
+        let mut best: Vec<Option<(f64, &FareAttribute, usize)>> = vec![None; legs.len() + 1];
+        let total = |i: usize, best: &[Option<(f64, &FareAttribute, usize)>]| {
+            if i == legs.len() {
+                Some(0.0)
+            } else {
+                best[i].map(|b| b.0)
+            }
+        };
+
+        for start in (0..legs.len()).rev() {
+            for fare in self.fare_attributes.values() {
+                let max_legs = fare.max_transfers().map_or(legs.len(), |t| t + 1);
+                let mut fare_match = FareMatch::new(self, &fare.fare_id);
+
+                for end in start + 1..=(start + max_legs).min(legs.len()) {
+                    let leg = &legs[end - 1];
+                    let expired = match (
+                        fare.transfer_duration,
+                        legs[start].start_time,
+                        leg.start_time,
+                    ) {
+                        (Some(duration), Some(first), Some(t)) => t - first > duration as i64,
+                        _ => false,
+                    };
+                    if expired || !fare_match.push(leg) {
+                        break;
+                    }
+                    // The run may still pass through the rest of the fare's zones further on
+                    if !fare_match.matches() {
+                        continue;
+                    }
+
+                    let Some(rest) = total(end, &best) else {
+                        continue;
+                    };
+                    let candidate = fare.price + rest;
+                    // Ties go to the fare covering the most legs first, then to the lowest id
+                    let better = match best[start] {
+                        Some((current, current_fare, current_end)) => {
+                            candidate < current
+                                || (candidate == current
+                                    && (end, &current_fare.fare_id) > (current_end, &fare.fare_id))
+                        }
+                        None => true,
+                    };
+                    if better {
+                        best[start] = Some((candidate, fare, end));
+                    }
+                }
+            }
+        }
+
+        let mut quotes = Vec::new();
+        let mut start = 0;
+        while start < legs.len() {
+            let (_, fare, end) = best[start]?;
+            quotes.push(FareQuote {
+                fare_id: fare.fare_id.clone(),
+                price: fare.price,
+                currency_type: fare.currency_type.clone(),
+                legs: start..end,
+            });
+            start = end;
+        }
+
+        Some(quotes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::ScheduleLoader;
+
+    fn schedule() -> Schedule {
+        let attributes = "fare_id,price,currency_type,payment_method,transfers,transfer_duration\nlocal,2.90,USD,1,1,7200\nexpress,7.00,USD,1,0,\nzone_a,1.50,USD,0,,\ncity,2.00,USD,1,,\n";
+        // city is valid on the B62 anywhere, and on the Q20 for journeys through exactly zones A
+        // and B. The rule for an unknown fare is dropped
+        let rules = "fare_id,route_id,origin_id,destination_id,contains_id\nlocal,M15,,,\nlocal,M101,,,\nexpress,BxM1,,,\nzone_a,,,,A\ncity,B62,,,\ncity,Q20,,,A\ncity,Q20,,,B\nmissing,M15,,,\n";
+
+        ScheduleLoader::new()
+            .file("fare_attributes.txt", attributes.as_bytes())
+            .file("fare_rules.txt", rules.as_bytes())
+            .skip("agency.txt")
+            .skip("stops.txt")
+            .skip("calendar.txt")
+            .skip("routes.txt")
+            .skip("trips.txt")
+            .skip("stop_times.txt")
+            .load()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_fare_attributes() -> Result<(), csv::Error> {
+        let schedule = schedule();
+
+        let local = &schedule.fare_attributes["local"];
+        assert_eq!(local.price, 2.90);
+        assert_eq!(local.currency_type, "USD");
+        assert_eq!(local.payment_method, PaymentMethod::BeforeBoarding);
+        assert_eq!(local.transfers, Some(TransferLimit::OneTransfer));
+        assert_eq!(local.max_transfers(), Some(1));
+        assert_eq!(local.transfer_duration, Some(7200));
+
+        let zone_a = &schedule.fare_attributes["zone_a"];
+        assert_eq!(zone_a.transfers, None);
+        assert_eq!(zone_a.max_transfers(), None);
+
+        // Codes the spec may add later don't reject the row
+        let data = "fare_id,price,currency_type,payment_method,transfers\nfuture,3.00,USD,2,3\n";
+        let future: FareAttribute = csv::Reader::from_reader(data.as_bytes())
+            .deserialize()
+            .next()
+            .unwrap()?;
+        assert_eq!(future.payment_method, PaymentMethod::Unknown(2));
+        assert_eq!(future.transfers, Some(TransferLimit::Unknown(3)));
+        assert_eq!(future.max_transfers(), Some(3));
+
+        assert_eq!(schedule.fare_rules["local"].len(), 2);
+        assert_eq!(schedule.fare_rules["city"].len(), 3);
+        assert!(!schedule.fare_rules.contains_key("missing"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fares_for_leg() {
+        let schedule = schedule();
+
+        let fare_ids = |leg: FareLeg| {
+            schedule
+                .fares_for_leg(&leg)
+                .iter()
+                .map(|f| f.fare_id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(fare_ids(FareLeg::new("M15", None, None)), vec!["local"]);
+        assert_eq!(fare_ids(FareLeg::new("BxM1", None, None)), vec!["express"]);
+        assert_eq!(
+            fare_ids(FareLeg::new("M15", Some("A"), Some("A"))),
+            vec!["zone_a", "local"]
+        );
+        assert_eq!(
+            fare_ids(FareLeg::new("Q10", Some("A"), Some("B"))),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_calculate_fares() {
+        let schedule = schedule();
+
+        let summary = |legs: &[FareLeg]| {
+            schedule.calculate_fares(legs).map(|quotes| {
+                quotes
+                    .into_iter()
+                    .map(|q| (q.fare_id, q.legs))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        // One transfer is free, the second needs a new fare
+        let legs = [
+            FareLeg::new("M15", None, None),
+            FareLeg::new("M101", None, None),
+            FareLeg::new("M15", None, None),
+        ];
+        assert_eq!(
+            summary(&legs),
+            Some(vec![("local".to_owned(), 0..2), ("local".to_owned(), 2..3)])
+        );
+
+        // Express buses don't allow transfers
+        let legs = [
+            FareLeg::new("BxM1", None, None),
+            FareLeg::new("M15", None, None),
+        ];
+        assert_eq!(
+            summary(&legs),
+            Some(vec![
+                ("express".to_owned(), 0..1),
+                ("local".to_owned(), 1..2)
+            ])
+        );
+
+        // The zone fare is cheaper and has unlimited transfers within zone A
+        let legs = [
+            FareLeg::new("M15", Some("A"), Some("A")),
+            FareLeg::new("M101", Some("A"), Some("A")),
+            FareLeg::new("M15", Some("A"), Some("A")),
+        ];
+        assert_eq!(summary(&legs), Some(vec![("zone_a".to_owned(), 0..3)]));
+
+        // Transfers expire after two hours
+        let mut legs = [
+            FareLeg::new("M15", None, None),
+            FareLeg::new("M101", None, None),
+        ];
+        legs[0].start_time = Some(GtfsTime::from_hms(8, 0, 0));
+        legs[1].start_time = Some(GtfsTime::from_hms(10, 30, 0));
+        assert_eq!(
+            summary(&legs),
+            Some(vec![("local".to_owned(), 0..1), ("local".to_owned(), 1..2)])
+        );
+
+        assert_eq!(summary(&[FareLeg::new("Q10", None, None)]), None);
+        assert_eq!(summary(&[]), Some(vec![]));
+    }
+
+    #[test]
+    fn test_contains_zones() {
+        let schedule = schedule();
+
+        let fare_ids = |leg: FareLeg| {
+            schedule
+                .fares_for_leg(&leg)
+                .iter()
+                .map(|f| f.fare_id.clone())
+                .collect::<Vec<_>>()
+        };
+        let summary = |legs: &[FareLeg]| {
+            schedule.calculate_fares(legs).map(|quotes| {
+                quotes
+                    .into_iter()
+                    .map(|q| (q.fare_id, q.legs))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        // The route row applies outside the contains zones
+        assert_eq!(
+            fare_ids(FareLeg::new("B62", Some("C"), Some("D"))),
+            vec!["city"]
+        );
+        assert_eq!(
+            fare_ids(FareLeg::new("Q20", Some("A"), Some("B"))),
+            vec!["city"]
+        );
+        // Only part of the contains zones, or outside them
+        assert_eq!(
+            fare_ids(FareLeg::new("Q20", Some("A"), Some("A"))),
+            vec!["zone_a"]
+        );
+        assert!(fare_ids(FareLeg::new("Q20", Some("A"), Some("C"))).is_empty());
+
+        // Neither leg passes through both zones, the journey as a whole does
+        let legs = [
+            FareLeg::new("Q20", Some("A"), Some("A")),
+            FareLeg::new("Q20", Some("B"), Some("B")),
+        ];
+        assert_eq!(summary(&legs), Some(vec![("city".to_owned(), 0..2)]));
+
+        let legs = [
+            FareLeg::new("Q20", Some("A"), Some("B")),
+            FareLeg::new("Q20", Some("B"), Some("C")),
+        ];
+        assert_eq!(summary(&legs), None);
+    }
+}
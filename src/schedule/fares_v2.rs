@@ -0,0 +1,776 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use super::{Schedule, date::ServiceDate, time::GtfsTime};
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(from = "u32")]
+pub enum FareMediaType {
+    None, // No fare media involved, e.g. cash paid to the driver
+    PaperTicket,
+    TransitCard,
+    Cemv, // Contactless bank cards and devices
+    MobileApp,
+    Unknown(u32),
+}
+
+impl From<u32> for FareMediaType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::None,
+            1 | 2 => Self::PaperTicket, // 1 is deprecated, but equivalent
+            3 => Self::TransitCard,
+            4 => Self::Cemv,
+            5 => Self::MobileApp,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<FareMediaType> for u32 {
+    fn from(value: FareMediaType) -> Self {
+        match value {
+            FareMediaType::None => 0,
+            FareMediaType::PaperTicket => 2,
+            FareMediaType::TransitCard => 3,
+            FareMediaType::Cemv => 4,
+            FareMediaType::MobileApp => 5,
+            FareMediaType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(from = "u32")]
+pub enum FareTransferType {
+    FromLegPlusTransfer,          // A + AB
+    FromLegPlusTransferPlusToLeg, // A + AB + B
+    TransferOnly,                 // AB
+    Unknown(u32),
+}
+
+impl From<u32> for FareTransferType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::FromLegPlusTransfer,
+            1 => Self::FromLegPlusTransferPlusToLeg,
+            2 => Self::TransferOnly,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<FareTransferType> for u32 {
+    fn from(value: FareTransferType) -> Self {
+        match value {
+            FareTransferType::FromLegPlusTransfer => 0,
+            FareTransferType::FromLegPlusTransferPlusToLeg => 1,
+            FareTransferType::TransferOnly => 2,
+            FareTransferType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(from = "u32")]
+pub enum DurationLimitType {
+    DepartureToArrival,   // Departure of the current leg to arrival of the next
+    DepartureToDeparture, // Departure of the current leg to departure of the next
+    ArrivalToDeparture,   // Arrival of the current leg to departure of the next
+    ArrivalToArrival,     // Arrival of the current leg to arrival of the next
+    Unknown(u32),
+}
+
+impl From<u32> for DurationLimitType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::DepartureToArrival,
+            1 => Self::DepartureToDeparture,
+            2 => Self::ArrivalToDeparture,
+            3 => Self::ArrivalToArrival,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<DurationLimitType> for u32 {
+    fn from(value: DurationLimitType) -> Self {
+        match value {
+            DurationLimitType::DepartureToArrival => 0,
+            DurationLimitType::DepartureToDeparture => 1,
+            DurationLimitType::ArrivalToDeparture => 2,
+            DurationLimitType::ArrivalToArrival => 3,
+            DurationLimitType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FareMedia {
+    pub fare_media_id: String,
+    pub fare_media_name: Option<String>,
+    pub fare_media_type: FareMediaType,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FareProduct {
+    pub fare_product_id: String,
+    pub fare_product_name: Option<String>,
+    pub rider_category_id: Option<String>,
+    pub fare_media_id: Option<String>,
+    pub amount: f64,
+    pub currency: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FareLegRule {
+    pub leg_group_id: Option<String>,
+    pub network_id: Option<String>,
+    pub from_area_id: Option<String>,
+    pub to_area_id: Option<String>,
+    pub from_timeframe_group_id: Option<String>,
+    pub to_timeframe_group_id: Option<String>,
+    pub fare_product_id: String,
+    pub rule_priority: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FareTransferRule {
+    pub from_leg_group_id: Option<String>,
+    pub to_leg_group_id: Option<String>,
+    // -1 means unlimited, only set when both leg groups are the same
+    pub transfer_count: Option<i32>,
+    // In seconds
+    pub duration_limit: Option<u32>,
+    pub duration_limit_type: Option<DurationLimitType>,
+    pub fare_transfer_type: FareTransferType,
+    pub fare_product_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Area {
+    pub area_id: String,
+    pub area_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct StopArea {
+    pub area_id: String,
+    pub stop_id: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Network {
+    pub network_id: String,
+    pub network_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RouteNetwork {
+    pub network_id: String,
+    pub route_id: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Timeframe {
+    pub timeframe_group_id: String,
+    // Both default to the whole day, end_time is exclusive
+    pub start_time: Option<GtfsTime>,
+    pub end_time: Option<GtfsTime>,
+    pub service_id: String,
+}
+
+impl Timeframe {
+    /// Whether a time of day (below 24:00:00) falls within this timeframe
+    pub fn contains(&self, time: GtfsTime) -> bool {
+        self.start_time.is_none_or(|start| start <= time)
+            && time < self.end_time.unwrap_or(GtfsTime::from_hms(24, 0, 0))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RiderCategory {
+    pub rider_category_id: String,
+    pub rider_category_name: String,
+    pub is_default_fare_category: Option<u32>,
+    pub eligibility_url: Option<String>,
+}
+
+impl RiderCategory {
+    pub fn is_default(&self) -> bool {
+        self.is_default_fare_category == Some(1)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// One leg of a journey, as used for fare evaluation. A leg is a single ride, boarding at
+/// `from_stop_id` and alighting at `to_stop_id`
+pub struct JourneyLeg {
+    pub route_id: String,
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+    // Service date the times are relative to
+    pub date: ServiceDate,
+    pub departure_time: GtfsTime,
+    pub arrival_time: GtfsTime,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegFare {
+    pub leg_group_id: Option<String>,
+    pub fare_product_id: String,
+    pub amount: f64,
+    pub currency: String,
+    // Whether the leg is paid for separately, legs covered by a transfer aren't
+    pub charged: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferFare {
+    // Indices into the legs passed to `evaluate_fares`, `to_leg` is always `from_leg + 1`
+    pub from_leg: usize,
+    pub to_leg: usize,
+    pub fare_transfer_type: FareTransferType,
+    pub fare_product_id: Option<String>,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JourneyFare {
+    pub legs: Vec<LegFare>,
+    pub transfers: Vec<TransferFare>,
+    pub total: f64,
+    // Currency of the first leg, mixing currencies isn't supported
+    pub currency: String,
+}
+
+// Keeps the rules matching a leg on one field. Without priorities, rules with an empty field only
+// apply if no rule in the whole file matches the value exactly; with priorities, empty fields
+// always match
+fn filter_field<'a, T>(
+    candidates: Vec<&'a T>,
+    all: &[T],
+    field: impl Fn(&T) -> Option<&str>,
+    values: &HashSet<&str>,
+    wildcard: bool,
+) -> Vec<&'a T> {
+    let exact = |r: &T| field(r).is_some_and(|v| values.contains(v));
+    let empty_matches = wildcard || !all.iter().any(exact);
+
+    candidates
+        .into_iter()
+        .filter(|r| exact(r) || (empty_matches && field(r).is_none()))
+        .collect()
+}
+
+impl Schedule {
+    /// The network of a route, from route_networks.txt or the route's own `network_id`
+    pub fn route_network(&self, route_id: &str) -> Option<&str> {
+        self.route_networks
+            .get(route_id)
+            .map(|rn| rn.network_id.as_str())
+            .or_else(|| self.routes.get(route_id)?.network_id.as_deref())
+    }
+
+    /// Every area a stop belongs to, including the areas of its parent station
+    pub fn stop_area_ids(&self, stop_id: &str) -> HashSet<&str> {
+        let parent = self
+            .stops
+            .get(stop_id)
+            .and_then(|s| s.parent_station.as_deref());
+
+        [Some(stop_id), parent]
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.stop_areas.get(id))
+            .flatten()
+            .map(|sa| sa.area_id.as_str())
+            .collect()
+    }
+
+    /// Every timeframe group active at a given time. Times past midnight count towards the next
+    /// day's timeframes
+    pub fn timeframe_group_ids(&self, date: ServiceDate, time: GtfsTime) -> HashSet<&str> {
+        let day = GtfsTime::from_hms(24, 0, 0).secs();
+        let (date, time) = (
+            date + (time.secs() / day) as i32,
+            GtfsTime::from_secs(time.secs() % day),
+        );
+
+        self.timeframes
+            .iter()
+            .filter(|(_, timeframes)| {
+                timeframes
+                    .iter()
+                    .any(|tf| tf.contains(time) && self.is_service_active(&tf.service_id, date))
+            })
+            .map(|(id, _)| id.as_str())
+            .collect()
+    }
+
+    /// The leg rules that apply to a leg, following the spec's matching rules for empty fields and
+    /// `rule_priority`
+    pub fn matching_leg_rules(&self, leg: &JourneyLeg) -> Vec<&FareLegRule> {
+        let wildcard = self
+            .fare_leg_rules
+            .iter()
+            .any(|r| r.rule_priority.is_some());
+
+        let network: HashSet<&str> = self.route_network(&leg.route_id).into_iter().collect();
+        let from_areas = self.stop_area_ids(&leg.from_stop_id);
+        let to_areas = self.stop_area_ids(&leg.to_stop_id);
+        let from_timeframes = self.timeframe_group_ids(leg.date, leg.departure_time);
+        let to_timeframes = self.timeframe_group_ids(leg.date, leg.arrival_time);
+
+        let all = &self.fare_leg_rules;
+        let mut rules: Vec<&FareLegRule> = all.iter().collect();
+        rules = filter_field(rules, all, |r| r.network_id.as_deref(), &network, wildcard);
+        rules = filter_field(
+            rules,
+            all,
+            |r| r.from_area_id.as_deref(),
+            &from_areas,
+            wildcard,
+        );
+        rules = filter_field(rules, all, |r| r.to_area_id.as_deref(), &to_areas, wildcard);
+        rules = filter_field(
+            rules,
+            all,
+            |r| r.from_timeframe_group_id.as_deref(),
+            &from_timeframes,
+            wildcard,
+        );
+        rules = filter_field(
+            rules,
+            all,
+            |r| r.to_timeframe_group_id.as_deref(),
+            &to_timeframes,
+            wildcard,
+        );
+
+        if wildcard {
+            let top = rules.iter().map(|r| r.rule_priority.unwrap_or(0)).max();
+            rules.retain(|r| Some(r.rule_priority.unwrap_or(0)) == top);
+        }
+
+        rules
+    }
+
+    /// Cheapest price of a fare product for a rider category and fare media. Without a category,
+    /// the default category (or products without one) is used. Without a media, any media goes
+    pub fn fare_product_amount(
+        &self,
+        fare_product_id: &str,
+        rider_category_id: Option<&str>,
+        fare_media_id: Option<&str>,
+    ) -> Option<&FareProduct> {
+        let rider_matches =
+            |product: &FareProduct| match (rider_category_id, &product.rider_category_id) {
+                (Some(wanted), Some(category)) => wanted == category,
+                (Some(_), None) => false,
+                (None, Some(category)) => self
+                    .rider_categories
+                    .get(category)
+                    .is_some_and(RiderCategory::is_default),
+                (None, None) => true,
+            };
+
+        self.fare_products
+            .get(fare_product_id)?
+            .iter()
+            .filter(|p| rider_matches(p))
+            .filter(|p| fare_media_id.is_none_or(|m| p.fare_media_id.as_deref() == Some(m)))
+            .min_by(|a, b| a.amount.total_cmp(&b.amount))
+    }
+
+    fn matching_transfer_rule(
+        &self,
+        from: &LegFare,
+        to: &LegFare,
+        from_leg: &JourneyLeg,
+        to_leg: &JourneyLeg,
+        transfers_so_far: i32,
+    ) -> Option<&FareTransferRule> {
+        let from_group: HashSet<&str> = from.leg_group_id.as_deref().into_iter().collect();
+        let to_group: HashSet<&str> = to.leg_group_id.as_deref().into_iter().collect();
+
+        let all = &self.fare_transfer_rules;
+        let mut rules: Vec<&FareTransferRule> = all.iter().collect();
+        rules = filter_field(
+            rules,
+            all,
+            |r| r.from_leg_group_id.as_deref(),
+            &from_group,
+            false,
+        );
+        rules = filter_field(
+            rules,
+            all,
+            |r| r.to_leg_group_id.as_deref(),
+            &to_group,
+            false,
+        );
+
+        let absolute = |date: ServiceDate, time: GtfsTime| {
+            (date - from_leg.date) as i64 * 86400 + time.secs() as i64
+        };
+
+        rules.into_iter().find(|r| {
+            // There's no telling how a transfer type from a later spec combines the legs
+            if let FareTransferType::Unknown(_) = r.fare_transfer_type {
+                return false;
+            }
+
+            let count_ok = match r.transfer_count {
+                Some(-1) | None => true,
+                Some(count) => transfers_so_far < count,
+            };
+
+            let duration_ok = r.duration_limit.is_none_or(|limit| {
+                let (start, end) = match r
+                    .duration_limit_type
+                    .as_ref()
+                    .unwrap_or(&DurationLimitType::DepartureToArrival)
+                {
+                    DurationLimitType::DepartureToArrival => {
+                        (from_leg.departure_time, to_leg.arrival_time)
+                    }
+                    DurationLimitType::DepartureToDeparture => {
+                        (from_leg.departure_time, to_leg.departure_time)
+                    }
+                    DurationLimitType::ArrivalToDeparture => {
+                        (from_leg.arrival_time, to_leg.departure_time)
+                    }
+                    DurationLimitType::ArrivalToArrival => {
+                        (from_leg.arrival_time, to_leg.arrival_time)
+                    }
+                    // Nor what an unknown duration is measured between
+                    DurationLimitType::Unknown(_) => return false,
+                };
+
+                absolute(to_leg.date, end) - absolute(from_leg.date, start) <= limit as i64
+            });
+
+            count_ok && duration_ok
+        })
+    }
+
+    /// Evaluates the fare of a journey with Fares v2. Each leg is priced with its matching leg
+    /// rules, then consecutive legs are combined with transfer rules. Returns `None` if some leg
+    /// has no applicable fare
+    pub fn evaluate_fares(
+        &self,
+        legs: &[JourneyLeg],
+        rider_category_id: Option<&str>,
+        fare_media_id: Option<&str>,
+    ) -> Option<JourneyFare> {
+        let mut leg_fares: Vec<LegFare> = Vec::with_capacity(legs.len());
+        for leg in legs {
+            // Several rules can match, the cheapest one is used
+            let (rule, product) = self
+                .matching_leg_rules(leg)
+                .into_iter()
+                .filter_map(|r| {
+                    Some((
+                        r,
+                        self.fare_product_amount(
+                            &r.fare_product_id,
+                            rider_category_id,
+                            fare_media_id,
+                        )?,
+                    ))
+                })
+                .min_by(|a, b| a.1.amount.total_cmp(&b.1.amount))?;
+
+            leg_fares.push(LegFare {
+                leg_group_id: rule.leg_group_id.clone(),
+                fare_product_id: rule.fare_product_id.clone(),
+                amount: product.amount,
+                currency: product.currency.clone(),
+                charged: true,
+            });
+        }
+
+        let mut transfers: Vec<TransferFare> = Vec::new();
+        // Transfers since the last leg that was paid for on its own
+        let mut transfers_so_far = 0;
+        for i in 1..legs.len() {
+            let Some(rule) = self.matching_transfer_rule(
+                &leg_fares[i - 1],
+                &leg_fares[i],
+                &legs[i - 1],
+                &legs[i],
+                transfers_so_far,
+            ) else {
+                transfers_so_far = 0;
+                continue;
+            };
+
+            let amount = rule
+                .fare_product_id
+                .as_deref()
+                .and_then(|id| self.fare_product_amount(id, rider_category_id, fare_media_id))
+                .map_or(0.0, |p| p.amount);
+
+            match rule.fare_transfer_type {
+                FareTransferType::FromLegPlusTransfer => leg_fares[i].charged = false,
+                FareTransferType::FromLegPlusTransferPlusToLeg => {}
+                FareTransferType::TransferOnly => {
+                    leg_fares[i - 1].charged = false;
+                    leg_fares[i].charged = false;
+                }
+                // Never matched, see `matching_transfer_rule`
+                FareTransferType::Unknown(_) => {}
+            }
+
+            transfers.push(TransferFare {
+                from_leg: i - 1,
+                to_leg: i,
+                fare_transfer_type: rule.fare_transfer_type.clone(),
+                fare_product_id: rule.fare_product_id.clone(),
+                amount,
+            });
+            transfers_so_far += 1;
+        }
+
+        let total = leg_fares
+            .iter()
+            .filter(|l| l.charged)
+            .map(|l| l.amount)
+            .sum::<f64>()
+            + transfers.iter().map(|t| t.amount).sum::<f64>();
+        let currency = leg_fares
+            .first()
+            .map(|l| l.currency.clone())
+            .unwrap_or_default();
+
+        Some(JourneyFare {
+            legs: leg_fares,
+            transfers,
+            total,
+            currency,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::ScheduleLoader;
+
+    fn schedule() -> Schedule {
+        let stops = "stop_id,stop_name,parent_station\n127,Times Sq-42 St,\n127N,Times Sq-42 St,127\nJFK,JFK Airport,\n";
+        let routes = "route_id,route_type,network_id\n1,1,subway\nM15,3,\n";
+        let services = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250101,20251231\n";
+        let fare_media = "fare_media_id,fare_media_name,fare_media_type\nomny,OMNY,4\nmetrocard,MetroCard,3\nwallet,Wallet,9\n";
+        let route_networks = "network_id,route_id\nbus,M15\n";
+        let stop_areas = "area_id,stop_id\nmanhattan,127\nairport,JFK\n";
+        let timeframes =
+            "timeframe_group_id,start_time,end_time,service_id\npeak,07:00:00,10:00:00,Weekday\n";
+        let rider_categories = "rider_category_id,rider_category_name,is_default_fare_category\nadult,Adult,1\nreduced,Reduced Fare,0\n";
+        let fare_products = "fare_product_id,rider_category_id,fare_media_id,amount,currency\nbase,adult,,2.90,USD\nbase,reduced,,1.45,USD\npeak,adult,,3.50,USD\nairtrain,,,8.50,USD\nairtrain_transfer,,,5.60,USD\n";
+        let fare_leg_rules = "leg_group_id,network_id,from_area_id,to_area_id,from_timeframe_group_id,fare_product_id\nlocal,subway,,,,base\nlocal,bus,,,,base\npeak,subway,,,peak,peak\nairtrain,,,airport,,airtrain\n";
+        let fare_transfer_rules = "from_leg_group_id,to_leg_group_id,transfer_count,duration_limit,duration_limit_type,fare_transfer_type,fare_product_id\nlocal,local,1,7200,1,0,\nlocal,airtrain,,,,2,airtrain_transfer\nairtrain,local,,3600,7,3,\n";
+
+        ScheduleLoader::new()
+            .file("stops.txt", stops.as_bytes())
+            .file("routes.txt", routes.as_bytes())
+            .file("calendar.txt", services.as_bytes())
+            .file("fare_media.txt", fare_media.as_bytes())
+            .file("route_networks.txt", route_networks.as_bytes())
+            .file("stop_areas.txt", stop_areas.as_bytes())
+            .file("timeframes.txt", timeframes.as_bytes())
+            .file("rider_categories.txt", rider_categories.as_bytes())
+            .file("fare_products.txt", fare_products.as_bytes())
+            .file("fare_leg_rules.txt", fare_leg_rules.as_bytes())
+            .file("fare_transfer_rules.txt", fare_transfer_rules.as_bytes())
+            .skip("agency.txt")
+            .skip("trips.txt")
+            .skip("stop_times.txt")
+            .load()
+            .unwrap()
+    }
+
+    fn leg(route_id: &str, from: &str, to: &str, departure: &str, arrival: &str) -> JourneyLeg {
+        JourneyLeg {
+            route_id: route_id.to_owned(),
+            from_stop_id: from.to_owned(),
+            to_stop_id: to.to_owned(),
+            // A Monday
+            date: "20250217".parse().unwrap(),
+            departure_time: departure.parse().unwrap(),
+            arrival_time: arrival.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let schedule = schedule();
+        assert_eq!(
+            schedule.fare_media["omny"].fare_media_type,
+            FareMediaType::Cemv
+        );
+        assert_eq!(
+            schedule.fare_media["metrocard"].fare_media_type,
+            FareMediaType::TransitCard
+        );
+        assert_eq!(schedule.fare_products["base"].len(), 2);
+        assert_eq!(
+            schedule.fare_transfer_rules[0].duration_limit_type,
+            Some(DurationLimitType::DepartureToDeparture)
+        );
+        assert_eq!(
+            schedule.fare_transfer_rules[1].fare_transfer_type,
+            FareTransferType::TransferOnly
+        );
+        // Codes the spec may add later don't reject the row
+        assert_eq!(
+            schedule.fare_media["wallet"].fare_media_type,
+            FareMediaType::Unknown(9)
+        );
+        assert_eq!(
+            schedule.fare_transfer_rules[2].duration_limit_type,
+            Some(DurationLimitType::Unknown(7))
+        );
+        assert_eq!(
+            schedule.fare_transfer_rules[2].fare_transfer_type,
+            FareTransferType::Unknown(3)
+        );
+        assert!(schedule.rider_categories["adult"].is_default());
+        assert!(!schedule.rider_categories["reduced"].is_default());
+    }
+
+    #[test]
+    fn test_lookups() {
+        let schedule = schedule();
+
+        assert_eq!(schedule.route_network("1"), Some("subway"));
+        assert_eq!(schedule.route_network("M15"), Some("bus"));
+        assert_eq!(schedule.route_network("Q10"), None);
+
+        assert_eq!(schedule.stop_area_ids("127N"), HashSet::from(["manhattan"]));
+        assert!(schedule.stop_area_ids("101").is_empty());
+
+        let monday: ServiceDate = "20250217".parse().unwrap();
+        let peak = |date: ServiceDate, time: &str| {
+            schedule
+                .timeframe_group_ids(date, time.parse().unwrap())
+                .contains("peak")
+        };
+        assert!(peak(monday, "07:00:00"));
+        assert!(!peak(monday, "10:00:00"));
+        assert!(!peak(monday - 2, "08:00:00"));
+        // 32:00:00 on Sunday is 08:00:00 on Monday
+        assert!(peak(monday - 1, "32:00:00"));
+    }
+
+    #[test]
+    fn test_matching_leg_rules() {
+        let schedule = schedule();
+
+        let products = |leg: JourneyLeg| {
+            schedule
+                .matching_leg_rules(&leg)
+                .iter()
+                .map(|r| r.fare_product_id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // An exact timeframe match excludes the rule with an empty timeframe
+        assert_eq!(
+            products(leg("1", "127N", "101", "08:00:00", "08:30:00")),
+            vec!["peak"]
+        );
+        assert_eq!(
+            products(leg("1", "127N", "101", "11:00:00", "11:30:00")),
+            vec!["base"]
+        );
+        // Buses have no peak fare
+        assert_eq!(
+            products(leg("M15", "127", "101", "08:00:00", "08:30:00")),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            products(leg("M15", "127", "101", "11:00:00", "11:30:00")),
+            vec!["base"]
+        );
+        assert_eq!(
+            products(leg("Q10", "101", "JFK", "11:00:00", "11:30:00")),
+            vec!["airtrain"]
+        );
+        // The peak timeframe excludes every rule without one, even in other networks
+        assert!(products(leg("Q10", "101", "JFK", "08:00:00", "08:30:00")).is_empty());
+        assert!(products(leg("Q10", "101", "102", "11:00:00", "11:30:00")).is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_fares() {
+        let schedule = schedule();
+
+        // One free transfer within two hours, then a new fare
+        let legs = [
+            leg("1", "101", "102", "11:00:00", "11:20:00"),
+            leg("M15", "102", "103", "11:30:00", "11:50:00"),
+            leg("1", "103", "104", "12:00:00", "12:20:00"),
+        ];
+        let fare = schedule.evaluate_fares(&legs, None, None).unwrap();
+        assert_eq!(fare.total, 2.90 * 2.0);
+        assert_eq!(fare.currency, "USD");
+        assert_eq!(fare.transfers.len(), 1);
+        assert_eq!(
+            (fare.transfers[0].from_leg, fare.transfers[0].to_leg),
+            (0, 1)
+        );
+        assert_eq!(
+            fare.legs.iter().map(|l| l.charged).collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+
+        let reduced = schedule
+            .evaluate_fares(&legs, Some("reduced"), None)
+            .unwrap();
+        assert_eq!(reduced.total, 1.45 * 2.0);
+
+        // The transfer expired
+        let legs = [
+            leg("1", "101", "102", "11:00:00", "11:20:00"),
+            leg("M15", "102", "103", "13:30:00", "13:50:00"),
+        ];
+        let fare = schedule.evaluate_fares(&legs, None, None).unwrap();
+        assert!(fare.transfers.is_empty());
+        assert_eq!(fare.total, 2.90 * 2.0);
+
+        // Transfer-only fares replace both legs
+        let legs = [
+            leg("1", "101", "102", "11:00:00", "11:20:00"),
+            leg("Q10", "102", "JFK", "11:30:00", "11:50:00"),
+        ];
+        let fare = schedule.evaluate_fares(&legs, None, None).unwrap();
+        assert_eq!(fare.total, 5.60);
+        assert_eq!(
+            fare.transfers[0].fare_transfer_type,
+            FareTransferType::TransferOnly
+        );
+
+        assert!(
+            schedule
+                .evaluate_fares(
+                    &[leg("Q10", "101", "102", "11:00:00", "11:20:00")],
+                    None,
+                    None
+                )
+                .is_none()
+        );
+        assert!(schedule.evaluate_fares(&legs, None, Some("omny")).is_none());
+
+        // A transfer rule with an unknown type never applies
+        let legs = [
+            leg("Q10", "101", "JFK", "11:00:00", "11:30:00"),
+            leg("1", "127N", "101", "11:40:00", "12:00:00"),
+        ];
+        let fare = schedule.evaluate_fares(&legs, None, None).unwrap();
+        assert!(fare.transfers.is_empty());
+        assert_eq!(fare.total, 8.50 + 2.90);
+    }
+}
@@ -0,0 +1,76 @@
+use serde::Deserialize;
+
+use super::date::ServiceDate;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeedInfo {
+    pub feed_publisher_name: String,
+    pub feed_publisher_url: String,
+    pub feed_lang: String,
+    pub default_lang: Option<String>,
+    pub feed_start_date: Option<ServiceDate>,
+    pub feed_end_date: Option<ServiceDate>,
+    pub feed_version: Option<String>,
+    pub feed_contact_email: Option<String>,
+    pub feed_contact_url: Option<String>,
+}
+
+impl FeedInfo {
+    /// Whether `date` falls within the feed's validity window. Missing bounds are open-ended
+    pub fn is_valid_on(&self, date: ServiceDate) -> bool {
+        self.feed_start_date.is_none_or(|start| start <= date)
+            && self.feed_end_date.is_none_or(|end| date <= end)
+    }
+
+    /// Whether the feed's validity window ended before `date`
+    pub fn is_expired(&self, date: ServiceDate) -> bool {
+        self.feed_end_date.is_some_and(|end| end < date)
+    }
+
+    /// The validity window as date bounds, if the feed specifies both ends
+    pub fn date_bounds(&self) -> Option<(ServiceDate, ServiceDate)> {
+        Some((self.feed_start_date?, self.feed_end_date?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_info() -> Result<(), csv::Error> {
+        let data = "feed_publisher_name,feed_publisher_url,feed_lang,feed_start_date,feed_end_date,feed_version,feed_contact_email\nMTA New York City Transit,http://www.mta.info,en,20241216,20250606,2025-04-01,\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let mut res: Vec<FeedInfo> = Vec::new();
+
+        for rec in reader.deserialize() {
+            res.push(rec?);
+        }
+
+        assert_eq!(res.len(), 1);
+
+        let mta = res.pop().unwrap();
+        let date = |s: &str| s.parse::<ServiceDate>().unwrap();
+
+        assert_eq!(mta.feed_publisher_name, "MTA New York City Transit");
+        assert_eq!(mta.feed_publisher_url, "http://www.mta.info");
+        assert_eq!(mta.feed_lang, "en");
+        assert_eq!(mta.default_lang, None);
+        assert_eq!(mta.feed_start_date, Some(date("20241216")));
+        assert_eq!(mta.feed_end_date, Some(date("20250606")));
+        assert_eq!(mta.feed_version, Some("2025-04-01".to_owned()));
+        assert_eq!(mta.feed_contact_email, None);
+        assert_eq!(mta.feed_contact_url, None);
+
+        assert_eq!(
+            mta.date_bounds(),
+            Some((date("20241216"), date("20250606")))
+        );
+        assert!(mta.is_valid_on(date("20250217")));
+        assert!(!mta.is_valid_on(date("20241215")));
+        assert!(!mta.is_expired(date("20250606")));
+        assert!(mta.is_expired(date("20250607")));
+
+        Ok(())
+    }
+}
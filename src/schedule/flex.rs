@@ -0,0 +1,612 @@
+use std::{fmt, io::Read};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    Error, ParseContext, ParseError, Result, Schedule,
+    date::ServiceDate,
+    stop_times::{PickupType, StopTime},
+    time::GtfsTime,
+};
+use crate::geo::{BoundingBox, LatLon};
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(from = "u32")]
+pub enum BookingType {
+    RealTime,  // Can be booked up to the moment of travel
+    SameDay,   // Has to be booked on the same day, with some prior notice
+    PriorDays, // Has to be booked on an earlier day
+    Unknown(u32),
+}
+
+impl From<u32> for BookingType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::RealTime,
+            1 => Self::SameDay,
+            2 => Self::PriorDays,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<BookingType> for u32 {
+    fn from(value: BookingType) -> Self {
+        match value {
+            BookingType::RealTime => 0,
+            BookingType::SameDay => 1,
+            BookingType::PriorDays => 2,
+            BookingType::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BookingRule {
+    pub booking_rule_id: String,
+    pub booking_type: BookingType,
+    // In minutes
+    pub prior_notice_duration_min: Option<u32>,
+    pub prior_notice_duration_max: Option<u32>,
+    // In days
+    pub prior_notice_last_day: Option<u32>,
+    pub prior_notice_last_time: Option<GtfsTime>,
+    pub prior_notice_start_day: Option<u32>,
+    pub prior_notice_start_time: Option<GtfsTime>,
+    pub prior_notice_service_id: Option<String>,
+    pub message: Option<String>,
+    pub pickup_message: Option<String>,
+    pub drop_off_message: Option<String>,
+    pub phone_number: Option<String>,
+    pub info_url: Option<String>,
+    pub booking_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LocationGroup {
+    pub location_group_id: String,
+    pub location_group_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LocationGroupStop {
+    pub location_group_id: String,
+    pub stop_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    pub exterior: Vec<LatLon>,
+    pub holes: Vec<Vec<LatLon>>,
+}
+
+// Even-odd ray casting, treating coordinates as planar which is fine at the scale of service areas
+fn ring_contains(ring: &[LatLon], point: &LatLon) -> bool {
+    let mut inside = false;
+    for (i, a) in ring.iter().enumerate() {
+        let b = &ring[(i + ring.len() - 1) % ring.len()];
+        if (a.lat > point.lat) != (b.lat > point.lat)
+            && point.lon < (b.lon - a.lon) * (point.lat - a.lat) / (b.lat - a.lat) + a.lon
+        {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+impl Polygon {
+    pub fn contains(&self, point: &LatLon) -> bool {
+        ring_contains(&self.exterior, point)
+            && !self.holes.iter().any(|hole| ring_contains(hole, point))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A zone from locations.geojson, where riders can request pickups or drop offs
+pub struct Location {
+    pub location_id: String,
+    pub stop_name: Option<String>,
+    pub stop_desc: Option<String>,
+    // A Polygon geometry has one, a MultiPolygon several
+    pub polygons: Vec<Polygon>,
+}
+
+impl Location {
+    pub fn contains(&self, point: &LatLon) -> bool {
+        self.polygons.iter().any(|p| p.contains(point))
+    }
+
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::from_points(
+            self.polygons
+                .iter()
+                .flat_map(|p| p.exterior.iter().copied()),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct FeatureCollection {
+    features: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct Feature {
+    id: String,
+    #[serde(default)]
+    properties: Properties,
+    geometry: Geometry,
+}
+
+#[derive(Deserialize, Default)]
+struct Properties {
+    stop_name: Option<String>,
+    stop_desc: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Geometry {
+    #[serde(rename = "type")]
+    kind: String,
+    coordinates: Value,
+}
+
+type Ring = Vec<[f64; 2]>;
+
+fn polygon(rings: Vec<Ring>) -> Result<Polygon, String> {
+    // GeoJSON positions are longitude first
+    let mut rings = rings.into_iter().map(|ring| {
+        ring.into_iter()
+            .map(|[lon, lat]| LatLon::new(lat, lon))
+            .collect::<Result<Vec<_>, _>>()
+    });
+
+    Ok(Polygon {
+        exterior: rings.next().ok_or("Polygon without rings")??,
+        holes: rings.collect::<Result<_, _>>()?,
+    })
+}
+
+impl TryFrom<Feature> for Location {
+    type Error = String;
+
+    fn try_from(feature: Feature) -> Result<Self, Self::Error> {
+        let coordinates = feature.geometry.coordinates;
+        let polygons = match feature.geometry.kind.as_str() {
+            "Polygon" => vec![polygon(
+                serde_json::from_value(coordinates).map_err(|e| e.to_string())?,
+            )?],
+            "MultiPolygon" => serde_json::from_value::<Vec<Vec<Ring>>>(coordinates)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(polygon)
+                .collect::<Result<_, _>>()?,
+            kind => return Err(format!("Invalid geometry type: {}", kind)),
+        };
+
+        Ok(Self {
+            location_id: feature.id,
+            stop_name: feature.properties.stop_name,
+            stop_desc: feature.properties.stop_desc,
+            polygons,
+        })
+    }
+}
+
+const LOCATIONS_FILE: &str = "locations.geojson";
+
+fn locations_error(message: String) -> ParseError {
+    ParseError {
+        file: LOCATIONS_FILE,
+        record: None,
+        line: None,
+        column: None,
+        value: None,
+        message,
+    }
+}
+
+/// Reads locations.geojson. A malformed file is always an error, but in lenient mode features
+/// that can't be read are skipped. Records are numbered by feature, starting at 1
+pub(crate) fn read_locations<R: Read>(reader: R, ctx: &mut ParseContext) -> Result<Vec<Location>> {
+    let collection: FeatureCollection = serde_json::from_reader(reader).map_err(|e| {
+        Error::Parse(ParseError {
+            line: Some(e.line() as u64),
+            ..locations_error(e.to_string())
+        })
+    })?;
+
+    let mut locations = Vec::new();
+    for (i, value) in collection.features.into_iter().enumerate() {
+        let id = value.get("id").and_then(Value::as_str).map(str::to_owned);
+        let location = serde_json::from_value::<Feature>(value)
+            .map_err(|e| e.to_string())
+            .and_then(Location::try_from)
+            .map_err(|message| {
+                Error::Parse(ParseError {
+                    record: Some(i as u64 + 1),
+                    value: id,
+                    ..locations_error(message)
+                })
+            });
+
+        if let Some(location) = ctx.accept(location)? {
+            locations.push(location);
+        }
+    }
+
+    Ok(locations)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A reference to a record that doesn't exist
+pub struct ReferenceError {
+    pub file: &'static str,
+    // For stop_times.txt this is `trip_id/stop_sequence`
+    pub record_id: String,
+    pub field: &'static str,
+    pub value: String,
+}
+
+impl fmt::Display for ReferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} record {} refers to missing {} {}",
+            self.file, self.record_id, self.field, self.value
+        )
+    }
+}
+
+impl std::error::Error for ReferenceError {}
+
+impl Schedule {
+    /// Checks that every Flex reference resolves: locations, location groups and booking rules
+    /// from stop_times.txt, stops of location groups, and services of booking rules
+    pub fn validate_flex_references(&self) -> Vec<ReferenceError> {
+        let mut errors: Vec<ReferenceError> = Vec::new();
+
+        for stop_time in self.stop_times.values().flatten() {
+            let record_id = format!("{}/{}", stop_time.trip_id, stop_time.stop_sequence);
+            let references = [
+                (
+                    "location_id",
+                    &stop_time.location_id,
+                    self.locations
+                        .contains_key(stop_time.location_id.as_deref().unwrap_or("")),
+                ),
+                (
+                    "location_group_id",
+                    &stop_time.location_group_id,
+                    self.location_groups
+                        .contains_key(stop_time.location_group_id.as_deref().unwrap_or("")),
+                ),
+                (
+                    "pickup_booking_rule_id",
+                    &stop_time.pickup_booking_rule_id,
+                    self.booking_rules
+                        .contains_key(stop_time.pickup_booking_rule_id.as_deref().unwrap_or("")),
+                ),
+                (
+                    "drop_off_booking_rule_id",
+                    &stop_time.drop_off_booking_rule_id,
+                    self.booking_rules
+                        .contains_key(stop_time.drop_off_booking_rule_id.as_deref().unwrap_or("")),
+                ),
+            ];
+
+            for (field, value, exists) in references {
+                if let (Some(value), false) = (value, exists) {
+                    errors.push(ReferenceError {
+                        file: "stop_times.txt",
+                        record_id: record_id.clone(),
+                        field,
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+
+        for group_stop in self.location_group_stops.values().flatten() {
+            let record_id = format!("{}/{}", group_stop.location_group_id, group_stop.stop_id);
+            if !self
+                .location_groups
+                .contains_key(&group_stop.location_group_id)
+            {
+                errors.push(ReferenceError {
+                    file: "location_group_stops.txt",
+                    record_id: record_id.clone(),
+                    field: "location_group_id",
+                    value: group_stop.location_group_id.clone(),
+                });
+            }
+            if !self.stops.contains_key(&group_stop.stop_id) {
+                errors.push(ReferenceError {
+                    file: "location_group_stops.txt",
+                    record_id,
+                    field: "stop_id",
+                    value: group_stop.stop_id.clone(),
+                });
+            }
+        }
+
+        for rule in self.booking_rules.values() {
+            let Some(service_id) = &rule.prior_notice_service_id else {
+                continue;
+            };
+            if !self.services.contains_key(service_id)
+                && !self.service_exceptions.contains_key(service_id)
+            {
+                errors.push(ReferenceError {
+                    file: "booking_rules.txt",
+                    record_id: rule.booking_rule_id.clone(),
+                    field: "prior_notice_service_id",
+                    value: service_id.clone(),
+                });
+            }
+        }
+
+        errors
+            .sort_by(|a, b| (a.file, &a.record_id, a.field).cmp(&(b.file, &b.record_id, b.field)));
+        errors
+    }
+
+    /// Every on-demand stop time that allows a pickup at `point` at `time` on `date`, i.e. whose
+    /// location zone contains the point and whose pickup window contains the time. Only zones
+    /// from locations.geojson are considered, location groups are sets of fixed stops
+    pub fn on_demand_pickups(
+        &self,
+        point: &LatLon,
+        date: ServiceDate,
+        time: GtfsTime,
+    ) -> Vec<&StopTime> {
+        let mut pickups: Vec<&StopTime> = self
+            .trips
+            .values()
+            .filter(|t| self.is_service_active(&t.service_id, date))
+            .filter_map(|t| self.stop_times.get(&t.trip_id))
+            .flatten()
+            .filter(|st| {
+                let in_window = match (
+                    st.start_pickup_drop_off_window,
+                    st.end_pickup_drop_off_window,
+                ) {
+                    (Some(start), Some(end)) => start <= time && time <= end,
+                    _ => false,
+                };
+
+                in_window
+                    && st.pickup_type != Some(PickupType::NoPickup)
+                    && st
+                        .location_id
+                        .as_ref()
+                        .and_then(|id| self.locations.get(id))
+                        .is_some_and(|location| location.contains(point))
+            })
+            .collect();
+
+        pickups.sort_by(|a, b| (&a.trip_id, a.stop_sequence).cmp(&(&b.trip_id, b.stop_sequence)));
+        pickups
+    }
+
+    /// Whether any on-demand service picks up at `point` at `time` on `date`
+    pub fn is_on_demand_pickup_available(
+        &self,
+        point: &LatLon,
+        date: ServiceDate,
+        time: GtfsTime,
+    ) -> bool {
+        !self.on_demand_pickups(point, date, time).is_empty()
+    }
+
+    /// The booking rule for picking up at a stop time, if it needs booking
+    pub fn pickup_booking_rule(&self, stop_time: &StopTime) -> Option<&BookingRule> {
+        self.booking_rules
+            .get(stop_time.pickup_booking_rule_id.as_deref()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::ScheduleLoader;
+
+    const LOCATIONS: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "id": "midtown",
+                "type": "Feature",
+                "properties": { "stop_name": "Midtown zone" },
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [
+                        [[-74.00, 40.74], [-73.96, 40.74], [-73.96, 40.78], [-74.00, 40.78], [-74.00, 40.74]],
+                        [[-73.99, 40.75], [-73.98, 40.75], [-73.98, 40.76], [-73.99, 40.76], [-73.99, 40.75]]
+                    ]
+                }
+            },
+            {
+                "id": "islands",
+                "type": "Feature",
+                "geometry": {
+                    "type": "MultiPolygon",
+                    "coordinates": [
+                        [[[-74.02, 40.68], [-74.01, 40.68], [-74.01, 40.69], [-74.02, 40.69], [-74.02, 40.68]]],
+                        [[[-73.93, 40.76], [-73.92, 40.76], [-73.92, 40.77], [-73.93, 40.77], [-73.93, 40.76]]]
+                    ]
+                }
+            }
+        ]
+    }"#;
+
+    fn schedule() -> Schedule {
+        let trips = "route_id,trip_id,service_id\nFLEX,weekday,Weekday\nFLEX,weekend,Weekend\n";
+        let stop_times = "trip_id,location_id,location_group_id,stop_sequence,start_pickup_drop_off_window,end_pickup_drop_off_window,pickup_type,drop_off_type,pickup_booking_rule_id\n\
+            weekday,midtown,,1,07:00:00,19:00:00,2,2,call\n\
+            weekday,islands,,2,07:00:00,19:00:00,1,2,\n\
+            weekend,islands,,1,10:00:00,16:00:00,2,2,missing\n\
+            weekend,,airports,2,10:00:00,16:00:00,2,2,\n";
+        let services = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n\
+            Weekday,1,1,1,1,1,0,0,20250101,20251231\n\
+            Weekend,0,0,0,0,0,1,1,20250101,20251231\n";
+        let booking_rules = "booking_rule_id,booking_type,prior_notice_duration_min,prior_notice_service_id,phone_number\n\
+            call,1,60,Weekday,555-0100\n\
+            ahead,2,,Holiday,\n\
+            app,3,,,\n";
+        let location_groups = "location_group_id,location_group_name\nhubs,Transit hubs\n";
+        let location_group_stops = "location_group_id,stop_id\nhubs,127\n";
+
+        ScheduleLoader::new()
+            .file("calendar.txt", services.as_bytes())
+            .file("trips.txt", trips.as_bytes())
+            .file("stop_times.txt", stop_times.as_bytes())
+            .file("booking_rules.txt", booking_rules.as_bytes())
+            .file("location_groups.txt", location_groups.as_bytes())
+            .file("location_group_stops.txt", location_group_stops.as_bytes())
+            .file("locations.geojson", LOCATIONS.as_bytes())
+            .skip("agency.txt")
+            .skip("stops.txt")
+            .skip("routes.txt")
+            .load()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_locations() {
+        let locations = read_locations(LOCATIONS.as_bytes(), &mut ParseContext::strict()).unwrap();
+        assert_eq!(locations.len(), 2);
+
+        let midtown = &locations[0];
+        assert_eq!(midtown.location_id, "midtown");
+        assert_eq!(midtown.stop_name, Some("Midtown zone".to_owned()));
+        assert_eq!(midtown.polygons[0].holes.len(), 1);
+        assert_eq!(
+            midtown.polygons[0].exterior[1],
+            LatLon::new(40.74, -73.96).unwrap()
+        );
+        assert!(midtown.contains(&LatLon::new(40.77, -73.97).unwrap()));
+        // Inside the hole
+        assert!(!midtown.contains(&LatLon::new(40.755, -73.985).unwrap()));
+        assert!(!midtown.contains(&LatLon::new(40.70, -73.97).unwrap()));
+
+        let islands = &locations[1];
+        assert_eq!(islands.polygons.len(), 2);
+        assert!(islands.contains(&LatLon::new(40.685, -74.015).unwrap()));
+        assert!(islands.contains(&LatLon::new(40.765, -73.925).unwrap()));
+        assert_eq!(
+            islands.bounding_box().map(|b| (b.min, b.max)),
+            Some((
+                LatLon::new(40.68, -74.02).unwrap(),
+                LatLon::new(40.77, -73.92).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_locations_errors() {
+        let data = r#"{"type": "FeatureCollection", "features": [
+            {"id": "a", "type": "Feature", "geometry": {"type": "Point", "coordinates": [-74.0, 40.7]}},
+            {"id": "b", "type": "Feature", "geometry": {"type": "Polygon", "coordinates": [[[-74.0, 40.7], [-73.9, 40.7], [-73.9, 40.8]]]}}
+        ]}"#;
+
+        let err = read_locations(data.as_bytes(), &mut ParseContext::strict()).unwrap_err();
+        let Error::Parse(err) = err else {
+            panic!("Expected parse error, got {:?}", err)
+        };
+        assert_eq!(err.file, "locations.geojson");
+        assert_eq!(err.record, Some(1));
+        assert_eq!(err.value, Some("a".to_owned()));
+        assert_eq!(err.message, "Invalid geometry type: Point");
+
+        let mut ctx = ParseContext::lenient();
+        let locations = read_locations(data.as_bytes(), &mut ctx).unwrap();
+        assert_eq!(locations.len(), 1);
+        assert_eq!(ctx.diagnostics.len(), 1);
+
+        let err = read_locations("{\"features\": [".as_bytes(), &mut ParseContext::lenient())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse(ParseError {
+                line: Some(1),
+                record: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_on_demand_pickups() {
+        let schedule = schedule();
+
+        let monday: ServiceDate = "20250217".parse().unwrap();
+        let saturday = monday + 5;
+        let midtown = LatLon::new(40.77, -73.97).unwrap();
+        let island = LatLon::new(40.685, -74.015).unwrap();
+        let noon = GtfsTime::from_hms(12, 0, 0);
+
+        let pickups = schedule.on_demand_pickups(&midtown, monday, noon);
+        assert_eq!(pickups.len(), 1);
+        assert_eq!(
+            (pickups[0].trip_id.as_str(), pickups[0].stop_sequence),
+            ("weekday", 1)
+        );
+        assert_eq!(
+            schedule
+                .pickup_booking_rule(pickups[0])
+                .map(|r| &r.booking_type),
+            Some(&BookingType::SameDay)
+        );
+
+        assert!(!schedule.is_on_demand_pickup_available(
+            &midtown,
+            monday,
+            GtfsTime::from_hms(20, 0, 0)
+        ));
+        assert!(!schedule.is_on_demand_pickup_available(&midtown, saturday, noon));
+        // Weekday service only drops off on the islands
+        assert!(!schedule.is_on_demand_pickup_available(&island, monday, noon));
+        assert!(schedule.is_on_demand_pickup_available(&island, saturday, noon));
+    }
+
+    #[test]
+    fn test_booking_rules() {
+        let schedule = schedule();
+
+        let call = &schedule.booking_rules["call"];
+        assert_eq!(call.booking_type, BookingType::SameDay);
+        assert_eq!(call.prior_notice_duration_min, Some(60));
+        // Codes the spec may add later don't reject the row
+        assert_eq!(
+            schedule.booking_rules["app"].booking_type,
+            BookingType::Unknown(3)
+        );
+        assert_eq!(u32::from(BookingType::Unknown(3)), 3);
+    }
+
+    #[test]
+    fn test_validate_flex_references() {
+        let mut schedule = schedule();
+        schedule.locations.remove("islands");
+
+        let errors: Vec<String> = schedule
+            .validate_flex_references()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "booking_rules.txt record ahead refers to missing prior_notice_service_id Holiday",
+                "location_group_stops.txt record hubs/127 refers to missing stop_id 127",
+                "stop_times.txt record weekday/2 refers to missing location_id islands",
+                "stop_times.txt record weekend/1 refers to missing location_id islands",
+                "stop_times.txt record weekend/1 refers to missing pickup_booking_rule_id missing",
+                "stop_times.txt record weekend/2 refers to missing location_group_id airports",
+            ]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use serde::Deserialize;
+
+use super::{Schedule, stop_times::StopTime, time::GtfsTime};
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(from = "u32")]
+pub enum ExactTimes {
+    FrequencyBased, // Headways are approximate, vehicles don't follow a fixed schedule
+    ScheduleBased,  // Trips depart exactly every headway_secs from start_time
+    Unknown(u32),
+}
+
+impl From<u32> for ExactTimes {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::FrequencyBased,
+            1 => Self::ScheduleBased,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<ExactTimes> for u32 {
+    fn from(value: ExactTimes) -> Self {
+        match value {
+            ExactTimes::FrequencyBased => 0,
+            ExactTimes::ScheduleBased => 1,
+            ExactTimes::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Frequency {
+    pub trip_id: String,
+    pub start_time: GtfsTime,
+    pub end_time: GtfsTime,
+    pub headway_secs: u32,
+    pub exact_times: Option<ExactTimes>,
+}
+
+impl Frequency {
+    /// Start times of every trip in this window. `end_time` is exclusive, as in the spec
+    pub fn departures(&self) -> impl Iterator<Item = GtfsTime> {
+        let (end_time, headway) = (self.end_time, self.headway_secs);
+
+        std::iter::successors(Some(self.start_time), move |t| {
+            // A headway of zero would otherwise never end
+            (headway > 0).then(|| *t + headway)
+        })
+        .take_while(move |t| *t < end_time)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A single run of a frequency-based trip, with stop times shifted to match its start time
+pub struct TripInstance {
+    pub trip_id: String,
+    pub start_time: GtfsTime,
+    // Ordered by stop_sequence
+    pub stop_times: Vec<StopTime>,
+}
+
+impl Schedule {
+    /// Expands a frequency-based trip into one instance per departure. The trip's own stop times
+    /// are used as a template, only the offsets between them matter. Trips without frequencies
+    /// or stop times expand to nothing
+    pub fn expand_frequencies(&self, trip_id: &str) -> Vec<TripInstance> {
+        let (Some(frequencies), Some(template)) =
+            (self.frequencies.get(trip_id), self.stop_times.get(trip_id))
+        else {
+            return Vec::new();
+        };
+
+        let Some(template_start) = template
+            .iter()
+            .find_map(|st| st.departure_time.or(st.arrival_time))
+        else {
+            return Vec::new();
+        };
+
+        let mut instances: Vec<TripInstance> = frequencies
+            .iter()
+            .flat_map(Frequency::departures)
+            .map(|start_time| {
+                let shift = |t: Option<GtfsTime>| {
+                    t.map(|t| {
+                        GtfsTime::from_secs(
+                            (t.secs() + start_time.secs()).saturating_sub(template_start.secs()),
+                        )
+                    })
+                };
+
+                TripInstance {
+                    trip_id: trip_id.to_owned(),
+                    start_time,
+                    stop_times: template
+                        .iter()
+                        .map(|st| StopTime {
+                            arrival_time: shift(st.arrival_time),
+                            departure_time: shift(st.departure_time),
+                            ..st.clone()
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        instances.sort_by_key(|i| i.start_time);
+        instances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frequencies() -> Result<(), csv::Error> {
+        let data = "trip_id,start_time,end_time,headway_secs,exact_times\nS1,06:00:00,07:00:00,600,1\nS1,23:50:00,24:20:00,900,\nS2,05:00:00,05:00:00,300,0\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let mut res: Vec<Frequency> = Vec::new();
+
+        for rec in reader.deserialize() {
+            res.push(rec?);
+        }
+
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0].exact_times, Some(ExactTimes::ScheduleBased));
+        assert_eq!(res[1].exact_times, None);
+        assert_eq!(res[1].end_time, GtfsTime::from_hms(24, 20, 0));
+
+        assert_eq!(res[0].departures().count(), 6);
+        assert_eq!(
+            res[1]
+                .departures()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>(),
+            vec!["23:50:00", "24:05:00"]
+        );
+        assert_eq!(res[2].departures().count(), 0);
+
+        // Codes the spec may add later don't reject the row
+        let data =
+            "trip_id,start_time,end_time,headway_secs,exact_times\nS1,06:00:00,07:00:00,600,2\n";
+        let frequency: Frequency = csv::Reader::from_reader(data.as_bytes())
+            .deserialize()
+            .next()
+            .unwrap()?;
+        assert_eq!(frequency.exact_times, Some(ExactTimes::Unknown(2)));
+        assert_eq!(u32::from(ExactTimes::Unknown(2)), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_frequencies() -> Result<(), csv::Error> {
+        let frequencies = "trip_id,start_time,end_time,headway_secs\nS1,08:00:00,08:30:00,600\n";
+        let stop_times = "trip_id,arrival_time,departure_time,stop_id,stop_sequence\nS1,00:00:00,00:00:00,902N,1\nS1,00:02:00,00:02:30,901N,2\nS1,00:05:00,00:05:00,723N,3\n";
+
+        let mut schedule = Schedule::default();
+        for rec in csv::Reader::from_reader(frequencies.as_bytes()).deserialize() {
+            let rec: Frequency = rec?;
+            schedule
+                .frequencies
+                .entry(rec.trip_id.clone())
+                .or_default()
+                .push(rec);
+        }
+        let mut template: Vec<StopTime> = Vec::new();
+        for rec in csv::Reader::from_reader(stop_times.as_bytes()).deserialize() {
+            template.push(rec?);
+        }
+        schedule.stop_times.insert("S1".to_owned(), template);
+
+        let instances = schedule.expand_frequencies("S1");
+
+        assert_eq!(instances.len(), 3);
+        assert_eq!(
+            instances
+                .iter()
+                .map(|i| i.start_time.to_string())
+                .collect::<Vec<_>>(),
+            vec!["08:00:00", "08:10:00", "08:20:00"]
+        );
+
+        let last = &instances[2];
+        assert_eq!(last.trip_id, "S1");
+        assert_eq!(
+            last.stop_times
+                .iter()
+                .map(|st| st.stop_id.clone().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["902N", "901N", "723N"]
+        );
+        assert_eq!(
+            last.stop_times[1].arrival_time,
+            Some(GtfsTime::from_hms(8, 22, 0))
+        );
+        assert_eq!(
+            last.stop_times[1].departure_time,
+            Some(GtfsTime::from_hms(8, 22, 30))
+        );
+        assert_eq!(
+            last.stop_times[2].arrival_time,
+            Some(GtfsTime::from_hms(8, 25, 0))
+        );
+
+        assert!(schedule.expand_frequencies("S2").is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    path::Path,
+};
+
+use super::{
+    ParseMode, Result, Schedule,
+    date::ServiceDate,
+    source::{DirSource, FeedSource, LoadOptions},
+};
+
+/// Builds a `Schedule` from individually supplied readers, each of which can be of a different
+/// type. Required files that are neither supplied nor skipped fail the load with
+/// `Error::MissingFile`
+///
+/// ```ignore
+/// let schedule = ScheduleLoader::new()
+///     .file("agency.txt", File::open("gtfs/agency.txt")?)
+///     .file("stops.txt", stops.as_bytes())
+///     // ...
+///     .date_bounds(start, end)
+///     .skip("shapes.txt")
+///     .load()?;
+/// ```
+#[derive(Default)]
+pub struct ScheduleLoader<'a> {
+    // Indexed by file name, e.g. "stops.txt"
+    readers: HashMap<&'static str, Box<dyn Read + 'a>>,
+    options: LoadOptions,
+}
+
+// Hands each reader out once, by file name
+struct Readers<'a>(HashMap<&'static str, Box<dyn Read + 'a>>);
+
+impl<'a> FeedSource for Readers<'a> {
+    type File<'b>
+        = Box<dyn Read + 'a>
+    where
+        Self: 'b;
+
+    fn open(&mut self, name: &'static str) -> Result<Option<Box<dyn Read + 'a>>> {
+        Ok(self.0.remove(name))
+    }
+}
+
+impl<'a> ScheduleLoader<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Supplies the contents of a GTFS file, named as in the feed, e.g. "stop_times.txt"
+    pub fn file<R>(mut self, name: &'static str, reader: R) -> Self
+    where
+        R: Read + 'a,
+    {
+        self.readers.insert(name, Box::new(reader));
+        self
+    }
+
+    pub fn options(mut self, options: LoadOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Only keep services and trips active between `start` and `end`, inclusive
+    pub fn date_bounds(mut self, start: ServiceDate, end: ServiceDate) -> Self {
+        self.options.date_bounds = Some((start, end));
+        self
+    }
+
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.options.mode = mode;
+        self
+    }
+
+    /// Only keep these routes, and the trips, stop times and shapes that belong to them
+    pub fn routes<I, S>(mut self, route_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.route_ids = Some(route_ids.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only keep these agencies and the routes they run
+    pub fn agencies<I, S>(mut self, agency_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.agency_ids = Some(agency_ids.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Leaves a file unloaded even if it's supplied or required
+    pub fn skip(mut self, name: &'static str) -> Self {
+        self.options.skip.insert(name);
+        self
+    }
+
+    /// Loads the supplied files
+    pub fn load(self) -> Result<Schedule> {
+        Schedule::from_source(Readers(self.readers), self.options)
+    }
+
+    /// Loads the files of another source with these options, ignoring any supplied files
+    pub fn load_from<S>(self, source: S) -> Result<Schedule>
+    where
+        S: FeedSource,
+    {
+        Schedule::from_source(source, self.options)
+    }
+
+    /// Loads a feed extracted into a directory with these options, ignoring any supplied files
+    pub fn load_dir<P: AsRef<Path>>(self, path: P) -> Result<Schedule> {
+        self.load_from(DirSource::new(path)?)
+    }
+
+    /// Loads the supplied files, parsing them on multiple threads. See
+    /// `Schedule::from_source_parallel`
+    #[cfg(feature = "parallel")]
+    pub fn load_parallel(self) -> Result<Schedule> {
+        Schedule::from_source_parallel(Readers(self.readers), self.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::schedule::Error;
+
+    const AGENCIES: &str = "agency_id,agency_name,agency_url,agency_timezone\nMTA NYCT,MTA New York City Transit,http://www.mta.info,America/New_York\nMTABC,MTA Bus Company,http://www.mta.info,America/New_York\n";
+    const STOPS: &str = "stop_id,stop_name\n101,Van Cortlandt Park-242 St\n";
+    const CALENDAR: &str = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250101,20251231\n";
+    const ROUTES: &str = "route_id,agency_id,route_type\n1,MTA NYCT,1\n2,MTA NYCT,1\nQ10,MTABC,3\n";
+    const TRIPS: &str = "route_id,trip_id,service_id,shape_id\n1,a,Weekday,1..N\n2,b,Weekday,2..N\nQ10,c,Weekday,Q10\n";
+    const STOP_TIMES: &str = "trip_id,stop_id,stop_sequence\na,101,1\nb,201,1\nc,501,1\n";
+    const SHAPES: &str = "shape_id,shape_pt_sequence,shape_pt_lat,shape_pt_lon\n1..N,1,40.889248,-73.898583\n2..N,1,40.903125,-73.85062\nQ10,1,40.7,-73.8\n";
+
+    fn loader<'a>() -> ScheduleLoader<'a> {
+        ScheduleLoader::new()
+            .file("agency.txt", AGENCIES.as_bytes())
+            // Any reader type will do
+            .file("stops.txt", Cursor::new(STOPS.to_owned()))
+            .file("calendar.txt", CALENDAR.as_bytes())
+            .file("routes.txt", ROUTES.as_bytes())
+            .file("trips.txt", TRIPS.as_bytes())
+            .file("stop_times.txt", STOP_TIMES.as_bytes())
+            .file("shapes.txt", SHAPES.as_bytes())
+    }
+
+    #[test]
+    fn test_loader() {
+        let schedule = loader().load().unwrap();
+        assert_eq!(schedule.agencies.len(), 2);
+        assert_eq!(schedule.trips.len(), 3);
+        assert_eq!(schedule.stop_times.len(), 3);
+        assert_eq!(schedule.shapes.len(), 3);
+
+        let schedule = loader().skip("shapes.txt").load().unwrap();
+        assert!(schedule.shapes.is_empty());
+        assert_eq!(schedule.trips.len(), 3);
+
+        let start = "20250101".parse().unwrap();
+        let end = "20250102".parse().unwrap();
+        let schedule = loader()
+            .date_bounds(start, end)
+            .mode(ParseMode::Lenient)
+            .load()
+            .unwrap();
+        assert_eq!(schedule.trips.len(), 3);
+    }
+
+    #[test]
+    fn test_loader_filters() {
+        let trip_ids = |schedule: &Schedule| {
+            let mut ids: Vec<&str> = schedule.trips.keys().map(String::as_str).collect();
+            ids.sort();
+            ids.join(",")
+        };
+
+        let schedule = loader().routes(["1"]).load().unwrap();
+        assert_eq!(trip_ids(&schedule), "a");
+        assert_eq!(schedule.routes.len(), 1);
+        assert_eq!(schedule.stop_times.keys().collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(schedule.shapes.keys().collect::<Vec<_>>(), vec!["1..N"]);
+
+        let schedule = loader().agencies(["MTA NYCT"]).load().unwrap();
+        assert_eq!(trip_ids(&schedule), "a,b");
+        assert_eq!(schedule.agencies.len(), 1);
+        assert_eq!(schedule.shapes.len(), 2);
+
+        let schedule = loader()
+            .agencies(["MTA NYCT"])
+            .routes(["Q10"])
+            .load()
+            .unwrap();
+        assert_eq!(trip_ids(&schedule), "");
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_loader_parallel() {
+        let schedule = loader().routes(["1", "Q10"]).load_parallel().unwrap();
+        let expected = loader().routes(["1", "Q10"]).load().unwrap();
+        assert_eq!(schedule.agencies.len(), expected.agencies.len());
+        assert_eq!(schedule.routes.len(), 2);
+        assert_eq!(schedule.trips.len(), 2);
+        assert_eq!(schedule.stop_times["c"][0].stop_id.as_deref(), Some("501"));
+        assert_eq!(schedule.shapes.len(), expected.shapes.len());
+
+        let err = ScheduleLoader::new()
+            .file("agency.txt", AGENCIES.as_bytes())
+            .load_parallel()
+            .unwrap_err();
+        assert!(matches!(err, Error::MissingFile("stops.txt")));
+    }
+
+    #[test]
+    fn test_loader_missing() {
+        let err = ScheduleLoader::new()
+            .file("agency.txt", AGENCIES.as_bytes())
+            .load()
+            .unwrap_err();
+        assert!(matches!(err, Error::MissingFile("stops.txt")));
+
+        // Skipped required files don't need to be supplied
+        let schedule = ScheduleLoader::new()
+            .file("agency.txt", AGENCIES.as_bytes())
+            .file("routes.txt", ROUTES.as_bytes())
+            .skip("stops.txt")
+            .skip("calendar.txt")
+            .skip("trips.txt")
+            .skip("stop_times.txt")
+            .load()
+            .unwrap();
+        assert_eq!(schedule.routes.len(), 3);
+        assert!(schedule.trips.is_empty());
+    }
+}
@@ -4,6 +4,9 @@ use std::{collections::HashMap, io::Read};
 
 use zip::ZipArchive;
 
+use super::{date::ServiceDate, time::GtfsTime};
+use crate::geo::LatLon;
+
 /// This module has a stripped down, type-enforced version of the spec that's even more catered to
 /// the current MTA format, as of April 2025
 const SUNDAY: u32 = 0;
@@ -33,14 +36,14 @@ pub enum SubwayService {
 }
 
 pub struct SubwayServiceNormal {
-    start_date: String,
-    end_date: String,
+    start_date: ServiceDate,
+    end_date: ServiceDate,
     days_active: [bool; 8],
     exceptions: Vec<SubwayServiceException>,
 }
 
 pub struct SubwayServiceException {
-    date: String,
+    date: ServiceDate,
     is_added: bool,
 }
 
@@ -56,21 +59,20 @@ pub struct SubwayTrip {
 
 pub struct SubwayStopTime {
     stop_id: String,
-    arrival_time: String,
-    departure_time: String,
+    arrival_time: GtfsTime,
+    departure_time: GtfsTime,
     stop_seqence: u32,
 }
 
 pub struct SubwayShape {
     shape_id: String,
-    latlons: Vec<(String, String)>,
+    latlons: Vec<LatLon>,
 }
 
 pub struct SubwayStop {
     stop_id: String,
     name: String,
-    lat: String,
-    lon: String,
+    location: LatLon,
     parent_stop_id: Option<String>,
     transfers_from: Vec<SubwayTransfer>,
 }
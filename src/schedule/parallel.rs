@@ -0,0 +1,362 @@
+#![cfg(feature = "parallel")]
+
+use std::{
+    collections::{HashMap, HashSet, hash_map::Entry},
+    io::Read,
+    path::Path,
+};
+
+use rayon::prelude::*;
+
+use super::{
+    Error, ParseContext, ParseDiagnostic, ParseError, ParseMode, Result, Schedule,
+    parse_stop_times, sort_stop_times,
+    source::{
+        DirSource, FEED_FILES, FeedFile, FeedSource, Files, LoadOptions, Stage, Store,
+        filter_routes,
+    },
+    stop_times::StopTime,
+    trips::Trip,
+};
+
+// stop_times.txt isn't split into chunks smaller than this
+const MIN_CHUNK_SIZE: usize = 1 << 20;
+
+type Contents = HashMap<&'static str, Vec<u8>>;
+type Parsed<T> = Result<(T, Vec<ParseDiagnostic>)>;
+
+// Reads every file into memory up front, so they can be parsed concurrently whatever the source
+fn read_files<S>(source: S, options: &LoadOptions) -> Result<Contents>
+where
+    S: FeedSource,
+{
+    let mut files = Files {
+        source,
+        skip: &options.skip,
+    };
+    let mut contents = Contents::new();
+
+    for file in &FEED_FILES {
+        if let Some(mut reader) = files.open(file)? {
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf)?;
+            contents.insert(file.name, buf);
+        }
+    }
+    files.require_calendar(
+        contents.contains_key("calendar.txt") || contents.contains_key("calendar_dates.txt"),
+    )?;
+
+    Ok(contents)
+}
+
+// Parses one file with its own context, files that weren't read have nothing to store
+fn parse(
+    contents: &Contents,
+    file: &FeedFile,
+    schedule: &Schedule,
+    options: &LoadOptions,
+) -> Parsed<Option<Store>> {
+    let Some(mut data) = contents.get(file.name).map(Vec::as_slice) else {
+        return Ok((None, Vec::new()));
+    };
+
+    let mut ctx = ParseContext::new(options.mode);
+    let store = (file.parse)(&mut data, schedule, options, &mut ctx)?;
+    Ok((Some(store), ctx.diagnostics))
+}
+
+// Parses every file of a stage concurrently, then stores them in `FEED_FILES` order so errors
+// and diagnostics come out the same as from `from_source`
+fn load_stage(
+    contents: &Contents,
+    stage: Stage,
+    schedule: &mut Schedule,
+    options: &LoadOptions,
+) -> Result<()> {
+    let parsed: Vec<Parsed<Option<Store>>> = FEED_FILES
+        .par_iter()
+        .filter(|file| file.stage == stage)
+        .map(|file| match file.name {
+            "stop_times.txt" => {
+                let (stop_times, diagnostics) = parse_stop_times_chunked(
+                    contents.get(file.name),
+                    &schedule.trips,
+                    options.mode,
+                )?;
+                let store: Store = Box::new(move |schedule| schedule.stop_times = stop_times);
+                Ok((Some(store), diagnostics))
+            }
+            _ => parse(contents, file, schedule, options),
+        })
+        .collect();
+
+    for file in parsed {
+        let (store, diagnostics) = file?;
+        schedule.diagnostics.extend(diagnostics);
+        if let Some(store) = store {
+            store(schedule);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+// A run of whole records of a CSV file, and how much of the file precedes it
+struct Chunk<'a> {
+    data: &'a [u8],
+    records_before: u64,
+    lines_before: u64,
+}
+
+// Splits the records of a CSV file into about `count` chunks, returning the header line and the
+// chunks. Newlines inside quoted fields don't end a record
+fn split_records(data: &[u8], count: usize) -> (&[u8], Vec<Chunk<'_>>) {
+    let target = (data.len() / count.max(1)).max(MIN_CHUNK_SIZE);
+
+    let mut header_end: Option<usize> = None;
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut lines = 0;
+    let mut records = 0;
+    let mut chunk_start = (0, 0);
+
+    for (i, &b) in data.iter().enumerate() {
+        match b {
+            b'"' => in_quotes = !in_quotes,
+            b'\n' => {
+                lines += 1;
+                if in_quotes {
+                    continue;
+                }
+
+                let Some(_) = header_end else {
+                    header_end = Some(i + 1);
+                    start = i + 1;
+                    chunk_start = (records, lines);
+                    continue;
+                };
+                records += 1;
+                if i + 1 - start >= target {
+                    chunks.push(Chunk {
+                        data: &data[start..i + 1],
+                        records_before: chunk_start.0,
+                        lines_before: chunk_start.1,
+                    });
+                    start = i + 1;
+                    chunk_start = (records, lines);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let header_end = header_end.unwrap_or(data.len());
+    let start = start.max(header_end);
+    if start < data.len() {
+        chunks.push(Chunk {
+            data: &data[start..],
+            records_before: chunk_start.0,
+            lines_before: chunk_start.1,
+        });
+    }
+
+    (&data[..header_end], chunks)
+}
+
+// Moves the position of an error in a chunk to its position in the whole file. The chunk is
+// parsed with the header in front of it, so its first record is on line 2
+fn shift(e: &mut ParseError, chunk: &Chunk) {
+    e.record = e.record.map(|r| r + chunk.records_before);
+    e.line = e.line.map(|l| l + chunk.lines_before - 1);
+}
+
+fn parse_stop_times_chunked(
+    data: Option<&Vec<u8>>,
+    trips: &HashMap<String, Trip>,
+    mode: ParseMode,
+) -> Parsed<HashMap<String, Vec<StopTime>>> {
+    let Some(data) = data else {
+        return Ok((HashMap::new(), Vec::new()));
+    };
+    let (header, chunks) = split_records(data, rayon::current_num_threads());
+
+    let parsed: Vec<Parsed<HashMap<String, Vec<StopTime>>>> = chunks
+        .par_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut ctx = ParseContext::new(mode);
+            let stop_times = parse_stop_times(header.chain(chunk.data), trips, &mut ctx).map_err(
+                |e| match e {
+                    Error::Parse(mut e) => {
+                        shift(&mut e, chunk);
+                        Error::Parse(e)
+                    }
+                    e => e,
+                },
+            )?;
+
+            let diagnostics = ctx
+                .diagnostics
+                .into_iter()
+                // A bad header is reported by every chunk, keep it once
+                .filter(|d| i == 0 || d.record.is_some())
+                .map(|mut d| {
+                    shift(&mut d, chunk);
+                    d
+                })
+                .collect();
+            Ok((stop_times, diagnostics))
+        })
+        .collect();
+
+    let mut stop_times: HashMap<String, Vec<StopTime>> = HashMap::new();
+    let mut diagnostics: Vec<ParseDiagnostic> = Vec::new();
+    // Trips split across chunks need sorting again
+    let mut split: HashSet<String> = HashSet::new();
+    for chunk in parsed {
+        let (chunk_stop_times, chunk_diagnostics) = chunk?;
+        diagnostics.extend(chunk_diagnostics);
+        for (trip_id, trip_stop_times) in chunk_stop_times {
+            match stop_times.entry(trip_id) {
+                Entry::Occupied(mut e) => {
+                    split.insert(e.key().clone());
+                    e.get_mut().extend(trip_stop_times);
+                }
+                Entry::Vacant(e) => {
+                    e.insert(trip_stop_times);
+                }
+            }
+        }
+    }
+    for trip_id in split {
+        if let Some(trip_stop_times) = stop_times.get_mut(&trip_id) {
+            sort_stop_times(trip_stop_times);
+        }
+    }
+
+    Ok((stop_times, diagnostics))
+}
+
+impl Schedule {
+    /// Like `from_source`, but parses files that don't depend on each other concurrently and
+    /// splits stop_times.txt into chunks parsed on every thread of the rayon pool. Every file is
+    /// read into memory before parsing starts
+    pub fn from_source_parallel<S>(source: S, options: LoadOptions) -> Result<Self>
+    where
+        S: FeedSource,
+    {
+        let contents = read_files(source, &options)?;
+        let mut schedule = Schedule::default();
+
+        load_stage(&contents, Stage::Independent, &mut schedule, &options)?;
+        load_stage(&contents, Stage::Services, &mut schedule, &options)?;
+        filter_routes(&mut schedule, &options);
+        load_stage(&contents, Stage::Trips, &mut schedule, &options)?;
+
+        Ok(schedule)
+    }
+
+    /// Parallel version of `from_dir`, see `from_source_parallel`
+    pub fn from_dir_parallel<P: AsRef<Path>>(path: P, options: LoadOptions) -> Result<Self> {
+        Self::from_source_parallel(DirSource::new(path)?, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_records() {
+        let data = b"trip_id,stop_id,stop_headsign\na,101,\"Two\nlines\"\na,103,\nb,101,";
+
+        let (header, chunks) = split_records(data, 4);
+        assert_eq!(header, b"trip_id,stop_id,stop_headsign\n");
+        // Small files aren't split
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].data, &data[header.len()..]);
+        assert_eq!(chunks[0].records_before, 0);
+        assert_eq!(chunks[0].lines_before, 1);
+
+        let (header, chunks) = split_records(b"trip_id,stop_id\n", 4);
+        assert_eq!(header, b"trip_id,stop_id\n");
+        assert!(chunks.is_empty());
+    }
+
+    fn stop_times(rows: usize) -> String {
+        let mut data = String::from("trip_id,stop_id,stop_sequence,stop_headsign\n");
+        for i in 0..rows {
+            // Trips are spread across the file, so most end up split between chunks
+            let trip = i % 97;
+            data.push_str(&format!(
+                "t{},{},{},\"Line {}\nHeadsign\"\n",
+                trip,
+                i % 500,
+                i,
+                trip
+            ));
+        }
+
+        data
+    }
+
+    #[test]
+    fn test_parallel_stop_times() {
+        let data = stop_times(100_000);
+        assert!(data.len() > 2 * MIN_CHUNK_SIZE);
+
+        let (_, chunks) = split_records(data.as_bytes(), 8);
+        assert!(chunks.len() > 1);
+        let records: usize = chunks
+            .iter()
+            .map(|c| c.data.iter().filter(|&&b| b == b'\n').count() / 2)
+            .sum();
+        assert_eq!(records, 100_000);
+
+        let trips: HashMap<String, Trip> = (0..97)
+            .map(|i| {
+                let trip = format!("route_id,trip_id,service_id\n1,t{},Weekday\n", i);
+                let trip: Trip = csv::Reader::from_reader(trip.as_bytes())
+                    .deserialize()
+                    .next()
+                    .unwrap()
+                    .unwrap();
+                (trip.trip_id.clone(), trip)
+            })
+            .collect();
+
+        let (parallel, _) =
+            parse_stop_times_chunked(Some(&data.clone().into_bytes()), &trips, ParseMode::Strict)
+                .unwrap();
+        let sequential =
+            parse_stop_times(data.as_bytes(), &trips, &mut ParseContext::strict()).unwrap();
+
+        assert_eq!(parallel.len(), sequential.len());
+        for (trip_id, stop_times) in &sequential {
+            let sequences =
+                |st: &[StopTime]| st.iter().map(|st| st.stop_sequence).collect::<Vec<_>>();
+            assert_eq!(sequences(&parallel[trip_id]), sequences(stop_times));
+        }
+    }
+
+    #[test]
+    fn test_parallel_error_position() {
+        let mut data = stop_times(100_000);
+        // The bad record is well past the first chunk, and every record before it is two lines
+        data.push_str("t1,101,x,\n");
+
+        let trips = HashMap::new();
+        let err = parse_stop_times_chunked(Some(&data.into_bytes()), &trips, ParseMode::Strict)
+            .unwrap_err();
+        let Error::Parse(err) = err else {
+            panic!("Expected parse error, got {:?}", err)
+        };
+
+        assert_eq!(err.record, Some(100_001));
+        assert_eq!(err.line, Some(200_002));
+        assert_eq!(err.column, Some("stop_sequence".to_owned()));
+    }
+}
@@ -0,0 +1,448 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use serde::Deserialize;
+
+use super::Schedule;
+
+// Typical walking speed in m/s, used when a pathway only has a length
+const WALKING_SPEED: f64 = 1.3;
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(from = "u32")]
+pub enum PathwayMode {
+    Walkway,
+    Stairs,
+    MovingSidewalk,
+    Escalator,
+    Elevator,
+    FareGate,
+    ExitGate,
+    Unknown(u32),
+}
+
+impl From<u32> for PathwayMode {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Self::Walkway,
+            2 => Self::Stairs,
+            3 => Self::MovingSidewalk,
+            4 => Self::Escalator,
+            5 => Self::Elevator,
+            6 => Self::FareGate,
+            7 => Self::ExitGate,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<PathwayMode> for u32 {
+    fn from(value: PathwayMode) -> Self {
+        match value {
+            PathwayMode::Walkway => 1,
+            PathwayMode::Stairs => 2,
+            PathwayMode::MovingSidewalk => 3,
+            PathwayMode::Escalator => 4,
+            PathwayMode::Elevator => 5,
+            PathwayMode::FareGate => 6,
+            PathwayMode::ExitGate => 7,
+            PathwayMode::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(from = "u32")]
+pub enum Directionality {
+    Unidirectional,
+    Bidirectional,
+    Unknown(u32),
+}
+
+impl From<u32> for Directionality {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::Unidirectional,
+            1 => Self::Bidirectional,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<Directionality> for u32 {
+    fn from(value: Directionality) -> Self {
+        match value {
+            Directionality::Unidirectional => 0,
+            Directionality::Bidirectional => 1,
+            Directionality::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Pathway {
+    pub pathway_id: String,
+    pub from_stop_id: String,
+    pub to_stop_id: String,
+    pub pathway_mode: PathwayMode,
+    pub is_bidirectional: Directionality,
+    // In meters
+    pub length: Option<f64>,
+    // In seconds
+    pub traversal_time: Option<u32>,
+    // Positive going up from from_stop_id, negative going down
+    pub stair_count: Option<i32>,
+    pub max_slope: Option<f64>,
+    // In meters
+    pub min_width: Option<f64>,
+    pub signposted_as: Option<String>,
+    pub reversed_signposted_as: Option<String>,
+}
+
+impl Pathway {
+    /// Time to walk the pathway in seconds, from `traversal_time` or estimated from `length`
+    pub fn duration(&self) -> Option<u32> {
+        self.traversal_time.or_else(|| {
+            self.length
+                .map(|length| (length / WALKING_SPEED).ceil() as u32)
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Level {
+    pub level_id: String,
+    // 0 is the ground level, negative below ground
+    pub level_index: f64,
+    pub level_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Restrictions on the pathways a walking path may use
+pub struct PathwayOptions {
+    pub avoid_stairs: bool,
+    pub avoid_escalators: bool,
+    // In meters, pathways narrower than this are avoided
+    pub min_width: Option<f64>,
+}
+
+impl PathwayOptions {
+    /// Only pathways usable with a wheelchair
+    pub fn accessible() -> Self {
+        Self {
+            avoid_stairs: true,
+            avoid_escalators: true,
+            min_width: None,
+        }
+    }
+
+    pub fn allows(&self, pathway: &Pathway) -> bool {
+        let stairs = pathway.pathway_mode == PathwayMode::Stairs
+            || pathway.stair_count.is_some_and(|c| c != 0);
+        let escalator = pathway.pathway_mode == PathwayMode::Escalator;
+
+        let avoided = (self.avoid_stairs && stairs) || (self.avoid_escalators && escalator);
+
+        !avoided
+            && self
+                .min_width
+                .is_none_or(|min| pathway.min_width.is_none_or(|w| w >= min))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PathwayEdge<'a> {
+    pub pathway: &'a Pathway,
+    pub to_stop_id: &'a str,
+    // Whether the pathway is walked from to_stop_id to from_stop_id
+    pub reversed: bool,
+}
+
+#[derive(Debug, Clone)]
+/// Every location within a station and the pathways between them
+pub struct StationGraph<'a> {
+    pub station_id: &'a str,
+    pub nodes: HashSet<&'a str>,
+    // Indexed by the stop_id the edge starts from
+    pub edges: HashMap<&'a str, Vec<PathwayEdge<'a>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkingPath {
+    // Every location visited, starting and ending with the requested stops
+    pub stop_ids: Vec<String>,
+    pub pathway_ids: Vec<String>,
+    // In seconds, pathways without a known duration count as zero
+    pub duration: u32,
+}
+
+impl<'a> StationGraph<'a> {
+    /// Fastest path between two locations of the station that only uses allowed pathways
+    pub fn shortest_path(
+        &self,
+        from_stop_id: &str,
+        to_stop_id: &str,
+        options: &PathwayOptions,
+    ) -> Option<WalkingPath> {
+        let from = *self.nodes.get(from_stop_id)?;
+        let to = *self.nodes.get(to_stop_id)?;
+
+        let mut durations: HashMap<&str, u32> = HashMap::from([(from, 0)]);
+        let mut previous: HashMap<&str, &PathwayEdge> = HashMap::new();
+        let mut queue = BinaryHeap::from([Reverse((0, from))]);
+
+        while let Some(Reverse((duration, stop_id))) = queue.pop() {
+            if stop_id == to {
+                break;
+            }
+            if durations.get(stop_id).is_some_and(|&d| d < duration) {
+                continue;
+            }
+
+            for edge in self.edges.get(stop_id).into_iter().flatten() {
+                if !options.allows(edge.pathway) {
+                    continue;
+                }
+
+                let next = duration + edge.pathway.duration().unwrap_or(0);
+                if durations.get(edge.to_stop_id).is_none_or(|&d| next < d) {
+                    durations.insert(edge.to_stop_id, next);
+                    previous.insert(edge.to_stop_id, edge);
+                    queue.push(Reverse((next, edge.to_stop_id)));
+                }
+            }
+        }
+
+        let duration = *durations.get(to)?;
+
+        let mut stop_ids = vec![to.to_owned()];
+        let mut pathway_ids = Vec::new();
+        let mut current = to;
+        while let Some(edge) = previous.get(current) {
+            pathway_ids.push(edge.pathway.pathway_id.clone());
+            current = if edge.reversed {
+                &edge.pathway.to_stop_id
+            } else {
+                &edge.pathway.from_stop_id
+            };
+            stop_ids.push(current.to_owned());
+        }
+        stop_ids.reverse();
+        pathway_ids.reverse();
+
+        Some(WalkingPath {
+            stop_ids,
+            pathway_ids,
+            duration,
+        })
+    }
+}
+
+impl Schedule {
+    pub fn stop_level(&self, stop_id: &str) -> Option<&Level> {
+        self.levels
+            .get(self.stops.get(stop_id)?.level_id.as_deref()?)
+    }
+
+    /// Builds the pathway graph of a station, including every location whose station it is
+    pub fn station_graph(&self, station_id: &str) -> Option<StationGraph<'_>> {
+        let (station_id, _) = self.stops.get_key_value(station_id)?;
+
+        let nodes: HashSet<&str> = self
+            .stops
+            .keys()
+            .filter(|id| {
+                self.station_of(id)
+                    .is_some_and(|station| &station.stop_id == station_id)
+            })
+            .map(String::as_str)
+            .collect();
+
+        let mut edges: HashMap<&str, Vec<PathwayEdge>> = HashMap::new();
+        for pathway in self.pathways.values() {
+            let (from, to) = (pathway.from_stop_id.as_str(), pathway.to_stop_id.as_str());
+            if !nodes.contains(from) || !nodes.contains(to) {
+                continue;
+            }
+
+            edges.entry(from).or_default().push(PathwayEdge {
+                pathway,
+                to_stop_id: to,
+                reversed: false,
+            });
+            if pathway.is_bidirectional == Directionality::Bidirectional {
+                edges.entry(to).or_default().push(PathwayEdge {
+                    pathway,
+                    to_stop_id: from,
+                    reversed: true,
+                });
+            }
+        }
+
+        Some(StationGraph {
+            station_id,
+            nodes,
+            edges,
+        })
+    }
+
+    /// Fastest walking path between two locations of the same station, e.g. from an entrance to a
+    /// platform
+    pub fn walking_path(
+        &self,
+        from_stop_id: &str,
+        to_stop_id: &str,
+        options: &PathwayOptions,
+    ) -> Option<WalkingPath> {
+        let station = self.station_of(from_stop_id)?;
+        if self.station_of(to_stop_id)?.stop_id != station.stop_id {
+            return None;
+        }
+
+        self.station_graph(&station.stop_id)?
+            .shortest_path(from_stop_id, to_stop_id, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::ScheduleLoader;
+
+    fn schedule() -> Schedule {
+        let stops = "stop_id,stop_name,location_type,parent_station,level_id\n\
+            A27,42 St-Port Authority Bus Terminal,1,,\n\
+            A27_E1,Entrance 1,2,A27,street\n\
+            A27_E2,Entrance 2,2,A27,street\n\
+            A27_MEZZ,Mezzanine,3,A27,mezzanine\n\
+            A27N,42 St-Port Authority Bus Terminal,0,A27,platform\n\
+            A27N_B,Boarding area,4,A27N,platform\n\
+            127,Times Sq-42 St,1,,\n\
+            127N,Times Sq-42 St,0,127,\n";
+        let pathways = "pathway_id,from_stop_id,to_stop_id,pathway_mode,is_bidirectional,length,traversal_time,stair_count\n\
+            P1,A27_E1,A27_MEZZ,2,1,,60,30\n\
+            P2,A27_E2,A27_MEZZ,5,1,,150,\n\
+            P3,A27_MEZZ,A27N,4,0,,30,\n\
+            P4,A27_MEZZ,A27N,2,1,13,,20\n\
+            P5,A27_MEZZ,A27N,5,1,,90,\n\
+            P6,A27_E1,A27_E2,1,1,130,,\n\
+            P7,A27N,A27N_B,1,1,,10,\n";
+        let levels = "level_id,level_index,level_name\nstreet,0,Street\nmezzanine,-1,Mezzanine\nplatform,-2,Platform\n";
+
+        ScheduleLoader::new()
+            .file("stops.txt", stops.as_bytes())
+            .file("pathways.txt", pathways.as_bytes())
+            .file("levels.txt", levels.as_bytes())
+            .skip("agency.txt")
+            .skip("calendar.txt")
+            .skip("routes.txt")
+            .skip("trips.txt")
+            .skip("stop_times.txt")
+            .load()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse() -> Result<(), csv::Error> {
+        let schedule = schedule();
+
+        let p1 = &schedule.pathways["P1"];
+        assert_eq!(p1.pathway_mode, PathwayMode::Stairs);
+        assert_eq!(p1.is_bidirectional, Directionality::Bidirectional);
+        assert_eq!(p1.stair_count, Some(30));
+        assert_eq!(p1.duration(), Some(60));
+        assert_eq!(schedule.pathways["P3"].pathway_mode, PathwayMode::Escalator);
+        assert_eq!(schedule.pathways["P4"].duration(), Some(10));
+
+        assert_eq!(
+            schedule.stop_level("A27N").map(|l| l.level_index),
+            Some(-2.0)
+        );
+        assert!(schedule.stop_level("A27").is_none());
+
+        // Codes the spec may add later don't reject the row
+        let data =
+            "pathway_id,from_stop_id,to_stop_id,pathway_mode,is_bidirectional\nP9,A27,A27N,8,2\n";
+        let pathway: Pathway = csv::Reader::from_reader(data.as_bytes())
+            .deserialize()
+            .next()
+            .unwrap()?;
+        assert_eq!(pathway.pathway_mode, PathwayMode::Unknown(8));
+        assert_eq!(pathway.is_bidirectional, Directionality::Unknown(2));
+        assert_eq!(u32::from(PathwayMode::Unknown(8)), 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_station_graph() {
+        let schedule = schedule();
+
+        assert_eq!(
+            schedule.station_of("A27N_B").map(|s| s.stop_id.as_str()),
+            Some("A27")
+        );
+        assert_eq!(
+            schedule.station_of("A27").map(|s| s.stop_id.as_str()),
+            Some("A27")
+        );
+
+        let graph = schedule.station_graph("A27").unwrap();
+        assert_eq!(graph.nodes.len(), 6);
+        assert!(!graph.nodes.contains("127N"));
+        // The escalator only goes down
+        assert_eq!(graph.edges["A27N"].len(), 3);
+        assert_eq!(graph.edges["A27_MEZZ"].len(), 5);
+    }
+
+    #[test]
+    fn test_walking_path() {
+        let schedule = schedule();
+
+        let path = |from: &str, to: &str, options: &PathwayOptions| {
+            schedule
+                .walking_path(from, to, options)
+                .map(|p| (p.pathway_ids, p.duration))
+        };
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        let fastest = schedule
+            .walking_path("A27_E1", "A27N_B", &PathwayOptions::default())
+            .unwrap();
+        assert_eq!(
+            fastest.stop_ids,
+            ids(&["A27_E1", "A27_MEZZ", "A27N", "A27N_B"])
+        );
+        assert_eq!(fastest.pathway_ids, ids(&["P1", "P4", "P7"]));
+        assert_eq!(fastest.duration, 80);
+
+        assert_eq!(
+            path(
+                "A27_E1",
+                "A27N",
+                &PathwayOptions {
+                    avoid_stairs: true,
+                    ..Default::default()
+                }
+            ),
+            Some((ids(&["P6", "P2", "P3"]), 280))
+        );
+        assert_eq!(
+            path("A27_E1", "A27N", &PathwayOptions::accessible()),
+            Some((ids(&["P6", "P2", "P5"]), 340))
+        );
+        // Going up, bidirectional pathways are walked in reverse
+        assert_eq!(
+            path("A27N", "A27_E2", &PathwayOptions::accessible()),
+            Some((ids(&["P5", "P2"]), 240))
+        );
+
+        assert_eq!(path("A27_E1", "127N", &PathwayOptions::default()), None);
+        assert_eq!(
+            path("A27_E1", "A27_E1", &PathwayOptions::default()),
+            Some((vec![], 0))
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::{fmt, io::Read, marker::PhantomData};
+
+use csv::StringRecord;
+use serde::{
+    Deserialize, Deserializer,
+    de::{DeserializeOwned, DeserializeSeed, MapAccess, Visitor, value::BorrowedStrDeserializer},
+    forward_to_deserialize_any,
+};
+
+use super::{Error, ParseError, Result};
+
+/// Lazily deserializes the records of a single GTFS file, attaching the file name, position,
+/// column and raw value to any record that fails to parse
+pub(crate) struct Records<T, R> {
+    file: &'static str,
+    reader: csv::Reader<R>,
+    headers: Option<StringRecord>,
+    record: StringRecord,
+    done: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<T, R> Records<T, R>
+where
+    R: Read,
+{
+    pub(crate) fn new(reader: R, file: &'static str) -> Self {
+        Self {
+            file,
+            reader: csv::Reader::from_reader(reader),
+            headers: None,
+            record: StringRecord::new(),
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, R> Iterator for Records<T, R>
+where
+    T: DeserializeOwned,
+    R: Read,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let headers = match &self.headers {
+            Some(headers) => headers,
+            None => match self.reader.headers() {
+                Ok(headers) => self.headers.insert(headers.clone()),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(Error::from_csv(self.file, e)));
+                }
+            },
+        };
+
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => match self.record.deserialize::<Tracked<T>>(Some(headers)) {
+                Ok(Tracked(Ok(rec))) => Some(Ok(rec)),
+                Ok(Tracked(Err(TrackedError { field, message }))) => {
+                    let position = self.record.position();
+                    Some(Err(ParseError {
+                        file: self.file,
+                        record: position.map(|p| p.record()),
+                        line: position.map(|p| p.line()),
+                        column: field.and_then(|i| headers.get(i)).map(str::to_owned),
+                        value: field.and_then(|i| self.record.get(i)).map(str::to_owned),
+                        message,
+                    }
+                    .into()))
+                }
+                Err(e) => Some(Err(Error::from_csv(self.file, e))),
+            },
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                // Malformed rows can be skipped over, but there's no recovering from IO errors
+                self.done = e.is_io_error();
+                Some(Err(Error::from_csv(self.file, e)))
+            }
+        }
+    }
+}
+
+// csv only reports the failing column for errors it raises itself, so errors coming from our own
+// conversions (e.g. an unknown enum code) lose it. To get it back we count map entries as serde
+// walks the record and catch the error before csv sees it
+struct Tracked<T>(std::result::Result<T, TrackedError>);
+
+struct TrackedError {
+    field: Option<usize>,
+    message: String,
+}
+
+impl<'de, T> Deserialize<'de> for Tracked<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut tracker = FieldTracker::default();
+        let res = T::deserialize(TrackingDeserializer {
+            inner: deserializer,
+            tracker: &mut tracker,
+        });
+
+        Ok(Self(res.map_err(|e| {
+            let message = e.to_string();
+            let message = match tracker.current {
+                Some(i) => message
+                    .strip_prefix(&format!("field {}: ", i))
+                    .map(str::to_owned)
+                    .unwrap_or(message),
+                None => message,
+            };
+
+            TrackedError {
+                field: tracker.current,
+                message,
+            }
+        })))
+    }
+}
+
+#[derive(Default)]
+struct FieldTracker {
+    // Number of keys seen so far
+    seen: usize,
+    // Index of the field whose value is currently being deserialized
+    current: Option<usize>,
+}
+
+struct TrackingDeserializer<'a, D> {
+    inner: D,
+    tracker: &'a mut FieldTracker,
+}
+
+impl<'de, D> Deserializer<'de> for TrackingDeserializer<'_, D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.inner.deserialize_any(TrackingVisitor {
+            inner: visitor,
+            tracker: self.tracker,
+        })
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.inner.deserialize_map(TrackingVisitor {
+            inner: visitor,
+            tracker: self.tracker,
+        })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.inner.deserialize_struct(
+            name,
+            fields,
+            TrackingVisitor {
+                inner: visitor,
+                tracker: self.tracker,
+            },
+        )
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct enum identifier
+        ignored_any
+    }
+}
+
+struct TrackingVisitor<'a, V> {
+    inner: V,
+    tracker: &'a mut FieldTracker,
+}
+
+impl<'de, V> Visitor<'de> for TrackingVisitor<'_, V>
+where
+    V: Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    fn visit_map<A>(self, map: A) -> std::result::Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        self.inner.visit_map(TrackingMap {
+            inner: map,
+            tracker: self.tracker,
+        })
+    }
+}
+
+struct TrackingMap<'a, A> {
+    inner: A,
+    tracker: &'a mut FieldTracker,
+}
+
+impl<'de, A> MapAccess<'de> for TrackingMap<'_, A>
+where
+    A: MapAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        // Headers are borrowed from the reader, so this doesn't allocate
+        let key = self.inner.next_key::<&'de str>()?;
+        key.map(|k| {
+            self.tracker.seen += 1;
+            seed.deserialize(BorrowedStrDeserializer::new(k))
+        })
+        .transpose()
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> std::result::Result<S::Value, Self::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.tracker.current = self.tracker.seen.checked_sub(1);
+        let value = self.inner.next_value_seed(seed)?;
+        self.tracker.current = None;
+
+        Ok(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
@@ -1,6 +1,9 @@
 use serde::Deserialize;
 
-use super::agency::Agency;
+use super::{
+    Schedule,
+    agency::{self, Agency},
+};
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
 #[serde(from = "u32")]
@@ -15,6 +18,9 @@ pub enum RouteType {
     Funicular,
     TrolleyBus,
     Monorail,
+    // Google's extended route types (100-1799), used by many European feeds
+    Extended(u32),
+    Unknown(u32),
 }
 
 impl From<u32> for RouteType {
@@ -30,7 +36,49 @@ impl From<u32> for RouteType {
             7 => Self::Funicular,
             11 => Self::TrolleyBus,
             12 => Self::Monorail,
-            _ => panic!("Invalid RouteType: {}", value),
+            100..=1799 => Self::Extended(value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<RouteType> for u32 {
+    fn from(value: RouteType) -> Self {
+        match value {
+            RouteType::LightRail => 0,
+            RouteType::Subway => 1,
+            RouteType::Rail => 2,
+            RouteType::Bus => 3,
+            RouteType::Ferry => 4,
+            RouteType::CableTram => 5,
+            RouteType::AerialLift => 6,
+            RouteType::Funicular => 7,
+            RouteType::TrolleyBus => 11,
+            RouteType::Monorail => 12,
+            RouteType::Extended(value) | RouteType::Unknown(value) => value,
+        }
+    }
+}
+
+impl RouteType {
+    /// Maps extended route types onto the closest basic route type. Basic types map to themselves,
+    /// and codes with no basic equivalent (air, taxi, etc.) map to `None`
+    pub fn basic(&self) -> Option<Self> {
+        match self {
+            Self::Extended(code) => match code {
+                100..=199 => Some(Self::Rail),
+                200..=299 | 700..=799 => Some(Self::Bus),
+                405 => Some(Self::Monorail),
+                400..=499 => Some(Self::Subway),
+                800..=899 => Some(Self::TrolleyBus),
+                900..=999 => Some(Self::LightRail),
+                1000..=1099 | 1200..=1299 => Some(Self::Ferry),
+                1300..=1399 => Some(Self::AerialLift),
+                1400..=1499 => Some(Self::Funicular),
+                _ => None,
+            },
+            Self::Unknown(_) => None,
+            basic => Some(basic.clone()),
         }
     }
 }
@@ -42,6 +90,7 @@ pub enum ContinuousType {
     NoContinuous,
     CallAgency,
     CallDriver,
+    Unknown(u32),
 }
 
 impl From<u32> for ContinuousType {
@@ -51,7 +100,19 @@ impl From<u32> for ContinuousType {
             1 => Self::NoContinuous,
             2 => Self::CallAgency,
             3 => Self::CallDriver,
-            _ => panic!("Invalid ContinuousType: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<ContinuousType> for u32 {
+    fn from(value: ContinuousType) -> Self {
+        match value {
+            ContinuousType::Continuous => 0,
+            ContinuousType::NoContinuous => 1,
+            ContinuousType::CallAgency => 2,
+            ContinuousType::CallDriver => 3,
+            ContinuousType::Unknown(value) => value,
         }
     }
 }
@@ -73,11 +134,76 @@ pub struct Route {
     pub network_id: Option<String>,
 }
 
+impl Route {
+    // Routes may leave agency_id empty when the feed has a single agency
+    pub(crate) fn agency_id_in<'a>(&'a self, agencies: &'a [Agency]) -> Option<&'a str> {
+        match &self.agency_id {
+            Some(agency_id) => Some(agency_id),
+            None => agency::sole_agency(agencies)?.agency_id.as_deref(),
+        }
+    }
+}
+
+impl Schedule {
+    /// The agency running a route, which is the feed's only agency if the route has no agency_id
+    pub fn route_agency_id(&self, route_id: &str) -> Option<&str> {
+        self.routes.get(route_id)?.agency_id_in(&self.agencies)
+    }
+}
+
+/// A `Route` borrowing its strings from the `csv::ByteRecord` it was deserialized from, see
+/// `StopRef`
+#[derive(Debug, Deserialize, Clone)]
+pub struct RouteRef<'a> {
+    pub route_id: &'a str,
+    #[serde(borrow)]
+    pub agency_id: Option<&'a str>,
+    #[serde(borrow)]
+    pub route_short_name: Option<&'a str>,
+    #[serde(borrow)]
+    pub route_long_name: Option<&'a str>,
+    #[serde(borrow)]
+    pub route_desc: Option<&'a str>,
+    pub route_type: RouteType,
+    #[serde(borrow)]
+    pub route_url: Option<&'a str>,
+    #[serde(borrow)]
+    pub route_color: Option<&'a str>,
+    #[serde(borrow)]
+    pub route_text_color: Option<&'a str>,
+    pub route_sort_order: Option<u32>,
+    pub continuous_pickup: Option<ContinuousType>,
+    pub continuous_drop_off: Option<ContinuousType>,
+    #[serde(borrow)]
+    pub network_id: Option<&'a str>,
+}
+
+impl From<RouteRef<'_>> for Route {
+    fn from(route: RouteRef) -> Self {
+        Self {
+            route_id: route.route_id.to_owned(),
+            agency_id: route.agency_id.map(str::to_owned),
+            route_short_name: route.route_short_name.map(str::to_owned),
+            route_long_name: route.route_long_name.map(str::to_owned),
+            route_desc: route.route_desc.map(str::to_owned),
+            route_type: route.route_type,
+            route_url: route.route_url.map(str::to_owned),
+            route_color: route.route_color.map(str::to_owned),
+            route_text_color: route.route_text_color.map(str::to_owned),
+            route_sort_order: route.route_sort_order,
+            continuous_pickup: route.continuous_pickup,
+            continuous_drop_off: route.continuous_drop_off,
+            network_id: route.network_id.map(str::to_owned),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
 
     use super::*;
+    use crate::schedule::ScheduleLoader;
 
     #[test]
     fn test_routes() -> Result<(), csv::Error> {
@@ -97,7 +223,7 @@ mod tests {
         assert_eq!(mta.agency_id, Some("MTA NYCT".to_owned()));
         assert_eq!(mta.route_short_name, Some("Z".to_owned()));
         assert_eq!(mta.route_long_name, Some("Nassau St Express".to_owned()));
-        assert!(mta.route_desc.is_some() && mta.route_desc.unwrap().len() > 0);
+        assert!(mta.route_desc.is_some() && !mta.route_desc.unwrap().is_empty());
         assert_eq!(mta.route_type, RouteType::Subway);
         assert_eq!(
             mta.route_url,
@@ -112,4 +238,93 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_route_type_codes() -> Result<(), csv::Error> {
+        let data = "route_id,route_type\nU1,400\nB1,715\nF1,1200\nA1,1100\nX1,42\nS1,1\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let mut res: Vec<Route> = Vec::new();
+
+        for rec in reader.deserialize() {
+            res.push(rec?);
+        }
+
+        let types: Vec<RouteType> = res.into_iter().map(|r| r.route_type).collect();
+
+        assert_eq!(
+            types,
+            vec![
+                RouteType::Extended(400),
+                RouteType::Extended(715),
+                RouteType::Extended(1200),
+                RouteType::Extended(1100),
+                RouteType::Unknown(42),
+                RouteType::Subway,
+            ]
+        );
+        assert_eq!(
+            types.iter().map(RouteType::basic).collect::<Vec<_>>(),
+            vec![
+                Some(RouteType::Subway),
+                Some(RouteType::Bus),
+                Some(RouteType::Ferry),
+                None,
+                None,
+                Some(RouteType::Subway),
+            ]
+        );
+        assert_eq!(
+            types.into_iter().map(u32::from).collect::<Vec<_>>(),
+            vec![400, 715, 1200, 1100, 42, 1]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_continuous_codes() -> Result<(), csv::Error> {
+        let data = "route_id,route_type,continuous_pickup,continuous_drop_off\nQ10,3,3,9\n";
+        let route: Route = csv::Reader::from_reader(data.as_bytes())
+            .deserialize()
+            .next()
+            .unwrap()?;
+
+        assert_eq!(route.continuous_pickup, Some(ContinuousType::CallDriver));
+        assert_eq!(route.continuous_drop_off, Some(ContinuousType::Unknown(9)));
+        assert_eq!(u32::from(ContinuousType::Unknown(9)), 9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_route_agency_id() {
+        let load = |agencies: &str| {
+            ScheduleLoader::new()
+                .file("agency.txt", agencies.as_bytes())
+                .file(
+                    "routes.txt",
+                    "route_id,agency_id,route_type\n1,MTA NYCT,1\nB62,,3\n".as_bytes(),
+                )
+                .skip("stops.txt")
+                .skip("calendar.txt")
+                .skip("trips.txt")
+                .skip("stop_times.txt")
+                .load()
+                .unwrap()
+        };
+
+        let schedule = load(
+            "agency_id,agency_name,agency_url,agency_timezone\nMTA NYCT,MTA New York City Transit,http://www.mta.info,America/New_York\n",
+        );
+        assert_eq!(schedule.route_agency_id("1"), Some("MTA NYCT"));
+        // Routes without an agency_id belong to the only agency
+        assert_eq!(schedule.route_agency_id("B62"), Some("MTA NYCT"));
+        assert_eq!(schedule.route_agency_id("Q10"), None);
+
+        let schedule = load(
+            "agency_id,agency_name,agency_url,agency_timezone\nMTA NYCT,MTA New York City Transit,http://www.mta.info,America/New_York\nMTABC,MTA Bus Company,http://www.mta.info,America/New_York\n",
+        );
+        assert_eq!(schedule.route_agency_id("1"), Some("MTA NYCT"));
+        assert_eq!(schedule.route_agency_id("B62"), None);
+    }
 }
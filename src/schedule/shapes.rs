@@ -1,10 +1,9 @@
-use std::collections::{
-    HashMap, VecDeque,
-    hash_map::{Entry, OccupiedEntry},
-};
+use std::collections::{HashMap, hash_map::Entry};
 
 use serde::{Deserialize, Serialize};
 
+use crate::geo::{self, BoundingBox, LatLon};
+
 #[derive(Debug, Deserialize, Clone, Copy, Serialize)]
 // Holds the base data for a point on a shape. Allows us to avoid storing the shape ID multiple times
 pub struct ShapePointData {
@@ -13,6 +12,15 @@ pub struct ShapePointData {
     pub shape_dist_traveled: Option<f64>,
 }
 
+impl ShapePointData {
+    pub fn location(&self) -> LatLon {
+        LatLon {
+            lat: self.shape_pt_lat,
+            lon: self.shape_pt_lon,
+        }
+    }
+}
+
 impl From<&ShapePoint> for ShapePointData {
     fn from(value: &ShapePoint) -> Self {
         Self {
@@ -27,7 +35,9 @@ impl From<&ShapePoint> for ShapePointData {
 pub struct ShapePoint {
     pub shape_id: String,
     pub shape_pt_sequence: u32,
+    #[serde(deserialize_with = "geo::deserialize_lat")]
     pub shape_pt_lat: f64,
+    #[serde(deserialize_with = "geo::deserialize_lon")]
     pub shape_pt_lon: f64,
     pub shape_dist_traveled: Option<f64>,
 }
@@ -35,30 +45,57 @@ pub struct ShapePoint {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Shape {
     pub shape_id: String,
+    // Ordered by shape_pt_sequence
     pub points: Vec<ShapePointData>,
+    // The original shape_pt_sequence of each point, only kept when requested
+    pub sequences: Option<Vec<u32>>,
 }
 
 impl Shape {
-    pub fn process_points(points: &Vec<ShapePoint>) -> HashMap<String, Self> {
-        let mut map = HashMap::new();
+    /// Groups points into shapes, ordering each shape by `shape_pt_sequence` so the input doesn't
+    /// need to be sorted
+    pub fn process_points(points: &[ShapePoint], keep_sequences: bool) -> HashMap<String, Self> {
+        let mut grouped: HashMap<&str, Vec<&ShapePoint>> = HashMap::new();
 
         for point in points {
-            match map.entry(point.shape_id.clone()) {
+            match grouped.entry(&point.shape_id) {
                 Entry::Occupied(mut e) => {
-                    let shape: &mut Shape = (e.get_mut());
-
-                    shape.points.insert(shape.points.len(), point.into());
+                    e.get_mut().push(point);
                 }
-                Entry::Vacant(mut e) => {
-                    e.insert(Shape {
-                        shape_id: point.shape_id.clone(),
-                        points: Vec::new(),
-                    });
+                Entry::Vacant(e) => {
+                    e.insert(vec![point]);
                 }
             }
         }
 
-        map
+        grouped
+            .into_iter()
+            .map(|(shape_id, mut points)| {
+                // Stable sort, so duplicate sequence numbers keep their file order
+                points.sort_by_key(|p| p.shape_pt_sequence);
+
+                let shape = Shape {
+                    shape_id: shape_id.to_owned(),
+                    points: points.iter().map(|&p| p.into()).collect(),
+                    sequences: keep_sequences
+                        .then(|| points.iter().map(|p| p.shape_pt_sequence).collect()),
+                };
+
+                (shape_id.to_owned(), shape)
+            })
+            .collect()
+    }
+
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::from_points(self.points.iter().map(ShapePointData::location))
+    }
+
+    /// Total length of the shape in meters, measured point to point
+    pub fn length(&self) -> f64 {
+        self.points
+            .windows(2)
+            .map(|w| w[0].location().distance(&w[1].location()))
+            .sum()
     }
 }
 
@@ -103,15 +140,42 @@ mod tests {
 
         assert_eq!(res.len(), 176482);
 
-        let mut shapes: Vec<Shape> = Shape::process_points(&res).into_values().collect();
+        let mut shapes: Vec<Shape> = Shape::process_points(&res, false).into_values().collect();
         shapes.sort_by_key(|s| s.shape_id.to_owned());
 
         assert_eq!(shapes.len(), 311);
 
         let mut mta = shapes.pop().unwrap();
 
-        assert_eq!(mta.points.len(), 689);
+        // Every row for the shape should make it in, including the first (sequence 0)
+        assert_eq!(mta.points.len(), 690);
         assert_eq!(mta.shape_id, "SI.S31R");
+        assert_eq!(mta.sequences, None);
+
+        let first = res
+            .iter()
+            .find(|p| p.shape_id == "SI.S31R" && p.shape_pt_sequence == 0)
+            .unwrap();
+        assert_eq!(mta.points[0].shape_pt_lat, first.shape_pt_lat);
+        assert_eq!(mta.points[0].shape_pt_lon, first.shape_pt_lon);
+
+        // Shuffled input gives the same shapes
+        let n = res.len();
+        let mut shuffled: Vec<(usize, ShapePoint)> = res.iter().cloned().enumerate().collect();
+        shuffled.sort_by_key(|(i, _)| (i * 104_729) % n);
+        let shuffled: Vec<ShapePoint> = shuffled.into_iter().map(|(_, p)| p).collect();
+        let shuffled = Shape::process_points(&shuffled, true);
+
+        assert_eq!(shuffled.len(), 311);
+        let shuffled_mta = &shuffled["SI.S31R"];
+        assert_eq!(shuffled_mta.points.len(), 690);
+        let sequences = shuffled_mta.sequences.as_ref().unwrap();
+        assert!(sequences.is_sorted());
+        assert_eq!((sequences[0], sequences[689]), (0, 689));
+        for (a, b) in shuffled_mta.points.iter().zip(&mta.points) {
+            assert_eq!(a.shape_pt_lat, b.shape_pt_lat);
+            assert_eq!(a.shape_pt_lon, b.shape_pt_lon);
+        }
 
         let point = mta.points.pop().unwrap();
 
@@ -121,4 +185,35 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_process_points_unordered() -> Result<(), csv::Error> {
+        let data = "shape_id,shape_pt_sequence,shape_pt_lat,shape_pt_lon\nA,3,40.3,-74.3\nB,1,41.1,-73.1\nA,1,40.1,-74.1\nA,2,40.2,-74.2\nB,0,41.0,-73.0\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let mut res: Vec<ShapePoint> = Vec::new();
+
+        for rec in reader.deserialize() {
+            res.push(rec?);
+        }
+
+        let shapes = Shape::process_points(&res, true);
+
+        assert_eq!(shapes.len(), 2);
+
+        let a = &shapes["A"];
+        assert_eq!(
+            a.points.iter().map(|p| p.shape_pt_lat).collect::<Vec<_>>(),
+            vec![40.1, 40.2, 40.3]
+        );
+        assert_eq!(a.sequences, Some(vec![1, 2, 3]));
+
+        let b = &shapes["B"];
+        assert_eq!(
+            b.points.iter().map(|p| p.shape_pt_lon).collect::<Vec<_>>(),
+            vec![-73.0, -73.1]
+        );
+        assert_eq!(b.sequences, Some(vec![0, 1]));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,513 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use super::{
+    Error, ParseContext, ParseMode, Result, Schedule, date::ServiceDate, parse_agencies,
+    parse_areas, parse_attributions, parse_booking_rules, parse_fare_attributes,
+    parse_fare_leg_rules, parse_fare_media, parse_fare_products, parse_fare_rules,
+    parse_fare_transfer_rules, parse_feed_info, parse_frequencies, parse_levels,
+    parse_location_group_stops, parse_location_groups, parse_locations, parse_networks,
+    parse_pathways, parse_rider_categories, parse_route_networks, parse_routes,
+    parse_service_exceptions, parse_services, parse_shapes, parse_stop_areas, parse_stop_times,
+    parse_stops, parse_timeframes, parse_transfers, parse_translations, parse_trips,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    // Only keep services and trips active within these dates, inclusive
+    pub date_bounds: Option<(ServiceDate, ServiceDate)>,
+    pub mode: ParseMode,
+    // Only keep these routes, and the trips, stop times and shapes that belong to them
+    pub route_ids: Option<HashSet<String>>,
+    // Only keep these agencies and their routes. Routes without an agency_id belong to the sole
+    // agency of the feed, if there is one
+    pub agency_ids: Option<HashSet<String>>,
+    // Files to leave unloaded, required files included. Skipped files are left empty
+    pub skip: HashSet<&'static str>,
+}
+
+/// Somewhere GTFS files can be opened by name, e.g. a zip archive or an extracted directory
+pub trait FeedSource {
+    type File<'a>: Read
+    where
+        Self: 'a;
+
+    /// Opens a file, returning `None` if the feed doesn't have it
+    fn open(&mut self, name: &'static str) -> Result<Option<Self::File<'_>>>;
+}
+
+/// A directory of extracted GTFS files
+#[derive(Debug, Clone)]
+pub struct DirSource {
+    path: PathBuf,
+}
+
+impl DirSource {
+    /// Fails if `path` isn't a directory, rather than reporting every file as missing
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if !fs::metadata(&path)?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", path.display()),
+            )
+            .into());
+        }
+
+        Ok(Self { path })
+    }
+}
+
+impl FeedSource for DirSource {
+    type File<'a> = File;
+
+    fn open(&mut self, name: &'static str) -> Result<Option<File>> {
+        match File::open(self.path.join(name)) {
+            Ok(file) => Ok(Some(file)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// When a file can be parsed, relative to the files it refers to. Stages are loaded in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Stage {
+    // Refers to no other file
+    Independent,
+    // Needs the services of calendar.txt and calendar_dates.txt
+    Services,
+    // Needs the trips, after the route and agency filters, or a file of an earlier stage
+    Trips,
+}
+
+// Stores what a `FeedFile` parsed in its field of the schedule
+pub(crate) type Store = Box<dyn FnOnce(&mut Schedule) + Send>;
+
+// A GTFS file the loaders know about, and how to parse it
+pub(crate) struct FeedFile {
+    pub(crate) name: &'static str,
+    // Required files are an `Error::MissingFile` when absent and not skipped. A feed also needs
+    // calendar.txt, calendar_dates.txt or both, see `Files::require_calendar`
+    pub(crate) required: bool,
+    pub(crate) stage: Stage,
+    // Parses the file against the files of earlier stages, already stored in the schedule
+    pub(crate) parse:
+        fn(&mut dyn Read, &Schedule, &LoadOptions, &mut ParseContext) -> Result<Store>,
+}
+
+// A `FeedFile` whose contents are parsed into `$field` of the schedule. `$parse` is called with
+// the reader, the schedule so far, the options and the parse context
+macro_rules! feed_file {
+    ($name:literal, $required:literal, $field:ident, $parse:path) => {
+        feed_file!(
+            $name,
+            $required,
+            Independent,
+            $field,
+            |reader, _, _, ctx| $parse(reader, ctx)
+        )
+    };
+    ($name:literal, $required:literal, $stage:ident, $field:ident, $parse:expr) => {
+        FeedFile {
+            name: $name,
+            required: $required,
+            stage: Stage::$stage,
+            parse: |reader, schedule, options, ctx| {
+                let parsed = $parse(reader, schedule, options, ctx)?;
+                let store: Store =
+                    Box::new(move |schedule: &mut Schedule| schedule.$field = parsed);
+                Ok(store)
+            },
+        }
+    };
+}
+
+// Every file both loaders read, grouped by stage. Diagnostics are reported in this order
+pub(crate) const FEED_FILES: [FeedFile; 31] = [
+    feed_file!("agency.txt", true, agencies, parse_agencies),
+    feed_file!("feed_info.txt", false, feed_info, parse_feed_info),
+    feed_file!("stops.txt", true, stops, parse_stops),
+    feed_file!(
+        "calendar.txt",
+        false,
+        Independent,
+        services,
+        |reader, _, options: &LoadOptions, ctx| {
+            parse_services(reader, options.date_bounds, ctx)
+        }
+    ),
+    feed_file!(
+        "calendar_dates.txt",
+        false,
+        Independent,
+        service_exceptions,
+        |reader, _, options: &LoadOptions, ctx| {
+            parse_service_exceptions(reader, options.date_bounds, ctx)
+        }
+    ),
+    feed_file!("routes.txt", true, routes, parse_routes),
+    feed_file!(
+        "shapes.txt",
+        false,
+        Independent,
+        shapes,
+        |reader, _, _, ctx| parse_shapes(reader, false, ctx)
+    ),
+    feed_file!("transfers.txt", false, transfers, parse_transfers),
+    feed_file!(
+        "fare_attributes.txt",
+        false,
+        fare_attributes,
+        parse_fare_attributes
+    ),
+    feed_file!("fare_media.txt", false, fare_media, parse_fare_media),
+    feed_file!(
+        "fare_products.txt",
+        false,
+        fare_products,
+        parse_fare_products
+    ),
+    feed_file!(
+        "fare_leg_rules.txt",
+        false,
+        fare_leg_rules,
+        parse_fare_leg_rules
+    ),
+    feed_file!(
+        "fare_transfer_rules.txt",
+        false,
+        fare_transfer_rules,
+        parse_fare_transfer_rules
+    ),
+    feed_file!("areas.txt", false, areas, parse_areas),
+    feed_file!("stop_areas.txt", false, stop_areas, parse_stop_areas),
+    feed_file!("networks.txt", false, networks, parse_networks),
+    feed_file!(
+        "route_networks.txt",
+        false,
+        route_networks,
+        parse_route_networks
+    ),
+    feed_file!("timeframes.txt", false, timeframes, parse_timeframes),
+    feed_file!(
+        "rider_categories.txt",
+        false,
+        rider_categories,
+        parse_rider_categories
+    ),
+    feed_file!("pathways.txt", false, pathways, parse_pathways),
+    feed_file!("levels.txt", false, levels, parse_levels),
+    feed_file!("translations.txt", false, translations, parse_translations),
+    feed_file!("locations.geojson", false, locations, parse_locations),
+    feed_file!(
+        "location_groups.txt",
+        false,
+        location_groups,
+        parse_location_groups
+    ),
+    feed_file!(
+        "location_group_stops.txt",
+        false,
+        location_group_stops,
+        parse_location_group_stops
+    ),
+    feed_file!(
+        "booking_rules.txt",
+        false,
+        booking_rules,
+        parse_booking_rules
+    ),
+    feed_file!("attributions.txt", false, attributions, parse_attributions),
+    feed_file!(
+        "trips.txt",
+        true,
+        Services,
+        trips,
+        |reader, schedule: &Schedule, options: &LoadOptions, ctx| {
+            parse_trips(
+                reader,
+                &schedule.services,
+                &schedule.service_exceptions,
+                options.date_bounds,
+                ctx,
+            )
+        }
+    ),
+    feed_file!(
+        "frequencies.txt",
+        false,
+        Trips,
+        frequencies,
+        |reader, schedule: &Schedule, _, ctx| parse_frequencies(reader, &schedule.trips, ctx)
+    ),
+    feed_file!(
+        "fare_rules.txt",
+        false,
+        Trips,
+        fare_rules,
+        |reader, schedule: &Schedule, _, ctx| {
+            parse_fare_rules(reader, &schedule.fare_attributes, ctx)
+        }
+    ),
+    feed_file!(
+        "stop_times.txt",
+        true,
+        Trips,
+        stop_times,
+        |reader, schedule: &Schedule, _, ctx| { parse_stop_times(reader, &schedule.trips, ctx) }
+    ),
+];
+
+// Opens files from a source, leaving out the ones the options skip
+pub(crate) struct Files<'o, S> {
+    pub(crate) source: S,
+    pub(crate) skip: &'o HashSet<&'static str>,
+}
+
+impl<S> Files<'_, S>
+where
+    S: FeedSource,
+{
+    pub(crate) fn optional(&mut self, name: &'static str) -> Result<Option<S::File<'_>>> {
+        if self.skip.contains(name) {
+            return Ok(None);
+        }
+
+        self.source.open(name)
+    }
+
+    pub(crate) fn open(&mut self, file: &FeedFile) -> Result<Option<S::File<'_>>> {
+        if file.required {
+            self.required(file.name)
+        } else {
+            self.optional(file.name)
+        }
+    }
+
+    // Only `None` when skipped
+    pub(crate) fn required(&mut self, name: &'static str) -> Result<Option<S::File<'_>>> {
+        if self.skip.contains(name) {
+            return Ok(None);
+        }
+
+        self.source
+            .open(name)?
+            .ok_or(Error::MissingFile(name))
+            .map(Some)
+    }
+
+    // Feeds can list their services in calendar.txt, calendar_dates.txt or both, so it's only an
+    // error if neither was found. Skipping either one allows both to be missing
+    pub(crate) fn require_calendar(&self, found: bool) -> Result<()> {
+        if found || self.skip.contains("calendar.txt") || self.skip.contains("calendar_dates.txt") {
+            return Ok(());
+        }
+
+        Err(Error::MissingFile("calendar.txt"))
+    }
+}
+
+// Applies the route and agency filters of the options, dropping the trips and shapes of any
+// routes filtered out. Must run before the files of `Stage::Trips` are parsed
+pub(crate) fn filter_routes(schedule: &mut Schedule, options: &LoadOptions) {
+    if options.route_ids.is_none() && options.agency_ids.is_none() {
+        return;
+    }
+
+    let Schedule {
+        agencies,
+        routes,
+        trips,
+        shapes,
+        ..
+    } = schedule;
+    if let Some(agency_ids) = &options.agency_ids {
+        routes.retain(|_, r| {
+            r.agency_id_in(agencies)
+                .is_some_and(|id| agency_ids.contains(id))
+        });
+        agencies.retain(|a| {
+            a.agency_id
+                .as_ref()
+                .is_some_and(|id| agency_ids.contains(id))
+        });
+    }
+    if let Some(route_ids) = &options.route_ids {
+        routes.retain(|id, _| route_ids.contains(id));
+    }
+
+    trips.retain(|_, t| routes.contains_key(&t.route_id));
+    let shape_ids: HashSet<&str> = trips
+        .values()
+        .filter_map(|t| t.shape_id.as_deref())
+        .collect();
+    shapes.retain(|id, _| shape_ids.contains(id.as_str()));
+}
+
+impl Schedule {
+    /// Loads every GTFS file present in `source`. Missing optional files are left empty, a missing
+    /// required file is an `Error::MissingFile` unless `options` skips it
+    pub fn from_source<S>(source: S, options: LoadOptions) -> Result<Self>
+    where
+        S: FeedSource,
+    {
+        let mut schedule = Schedule::default();
+        let mut ctx = ParseContext::new(options.mode);
+        let mut files = Files {
+            source,
+            skip: &options.skip,
+        };
+        let mut found: HashSet<&str> = HashSet::new();
+        let mut stage = Stage::Independent;
+
+        for file in &FEED_FILES {
+            if file.stage != stage {
+                stage = file.stage;
+                match stage {
+                    Stage::Independent => {}
+                    Stage::Services => files.require_calendar(
+                        found.contains("calendar.txt") || found.contains("calendar_dates.txt"),
+                    )?,
+                    Stage::Trips => filter_routes(&mut schedule, &options),
+                }
+            }
+
+            if let Some(mut reader) = files.open(file)? {
+                found.insert(file.name);
+                let store = (file.parse)(&mut reader, &schedule, &options, &mut ctx)?;
+                store(&mut schedule);
+            }
+        }
+
+        schedule.diagnostics = ctx.diagnostics;
+        Ok(schedule)
+    }
+
+    /// Loads a feed extracted into a directory, see `from_source`
+    pub fn from_dir<P: AsRef<Path>>(path: P, options: LoadOptions) -> Result<Self> {
+        Self::from_source(DirSource::new(path)?, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::*;
+
+    const FEED: [(&str, &str); 9] = [
+        (
+            "agency.txt",
+            "agency_name,agency_url,agency_timezone\nMTA,http://www.mta.info,America/New_York\n",
+        ),
+        (
+            "stops.txt",
+            "stop_id,stop_name\n101,Van Cortlandt Park-242 St\n",
+        ),
+        (
+            "calendar.txt",
+            "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nMarch,1,1,1,1,1,1,1,20250301,20250331\nApril,1,1,1,1,1,1,1,20250401,20250430\n",
+        ),
+        (
+            "calendar_dates.txt",
+            "service_id,date,exception_type\nApril,20250402,1\n",
+        ),
+        ("routes.txt", "route_id,route_type\n1,1\n"),
+        (
+            "trips.txt",
+            "route_id,trip_id,service_id\n1,march,March\n1,april,April\n",
+        ),
+        (
+            "stop_times.txt",
+            "trip_id,stop_id,stop_sequence\nmarch,101,1\napril,101,1\n",
+        ),
+        (
+            "levels.txt",
+            "level_id,level_index,level_name\nL0,0,Street\n",
+        ),
+        ("attributions.txt", "organization_name,is_producer\nMTA,1\n"),
+    ];
+
+    // Writes the feed, minus `skip`, to a fresh directory under the system temp dir
+    fn feed_dir(name: &str, skip: &[&str]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("gtfs-parsing-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in FEED.iter().filter(|(file, _)| !skip.contains(file)) {
+            fs::write(dir.join(file), contents).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn test_feed_files() {
+        // The loaders rely on each stage's files being listed together, and every file once
+        assert!(FEED_FILES.is_sorted_by_key(|file| file.stage));
+        let names: HashSet<&str> = FEED_FILES.iter().map(|file| file.name).collect();
+        assert_eq!(names.len(), FEED_FILES.len());
+    }
+
+    #[test]
+    fn test_from_dir() {
+        let dir = feed_dir("full", &[]);
+        let schedule = Schedule::from_dir(&dir, LoadOptions::default()).unwrap();
+
+        assert_eq!(schedule.trips.len(), 2);
+        assert_eq!(schedule.stop_times.len(), 2);
+        assert_eq!(schedule.levels["L0"].level_name, Some("Street".to_owned()));
+        assert_eq!(schedule.attributions.len(), 1);
+        // Optional files that aren't there are left empty
+        assert!(schedule.feed_info.is_none() && schedule.pathways.is_empty());
+
+        let options = LoadOptions {
+            date_bounds: Some(("20250301".parse().unwrap(), "20250331".parse().unwrap())),
+            ..Default::default()
+        };
+        let schedule = Schedule::from_dir(&dir, options).unwrap();
+        assert_eq!(schedule.trips.keys().collect::<Vec<_>>(), vec!["march"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_from_dir_missing() {
+        let dir = feed_dir("missing", &["stop_times.txt"]);
+        let err = Schedule::from_dir(&dir, LoadOptions::default()).unwrap_err();
+        assert!(matches!(err, Error::MissingFile("stop_times.txt")));
+
+        fs::remove_dir_all(&dir).unwrap();
+        let err = Schedule::from_dir(&dir, LoadOptions::default()).unwrap_err();
+        assert!(matches!(err, Error::Io(_)));
+    }
+
+    #[test]
+    fn test_from_dir_optional() {
+        // Shapes and transfers are optional, and calendar_dates.txt can stand in for calendar.txt
+        let dir = feed_dir("optional", &["calendar.txt"]);
+        let schedule = Schedule::from_dir(&dir, LoadOptions::default()).unwrap();
+        assert!(schedule.shapes.is_empty() && schedule.transfers.is_empty());
+        assert!(schedule.services.is_empty());
+        assert_eq!(schedule.trips.keys().collect::<Vec<_>>(), vec!["april"]);
+        assert_eq!(schedule.stop_times.len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // But one of the calendar files has to be there
+        let dir = feed_dir("no-calendar", &["calendar.txt", "calendar_dates.txt"]);
+        let err = Schedule::from_dir(&dir, LoadOptions::default()).unwrap_err();
+        assert!(matches!(err, Error::MissingFile("calendar.txt")));
+
+        let options = LoadOptions {
+            skip: HashSet::from(["calendar.txt"]),
+            ..Default::default()
+        };
+        let schedule = Schedule::from_dir(&dir, options).unwrap();
+        assert!(schedule.trips.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
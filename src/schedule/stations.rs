@@ -0,0 +1,397 @@
+use std::{collections::HashSet, fmt};
+
+use super::{
+    Schedule,
+    date::ServiceDate,
+    stop_times::PickupType,
+    stops::{LocationType, Stop},
+    time::GtfsTime,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A problem with the `parent_station` hierarchy of stops.txt
+pub enum HierarchyError {
+    // parent_station doesn't refer to an existing stop
+    MissingParent {
+        stop_id: String,
+        parent_station: String,
+    },
+    // Entrances, generic nodes and boarding areas need a parent
+    MissingRequiredParent {
+        stop_id: String,
+        location_type: LocationType,
+    },
+    // The parent has the wrong location_type, or the stop can't have a parent at all
+    IllegalParent {
+        stop_id: String,
+        location_type: LocationType,
+        parent_station: String,
+        parent_location_type: LocationType,
+    },
+}
+
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingParent {
+                stop_id,
+                parent_station,
+            } => write!(
+                f,
+                "Stop {} refers to missing parent station {}",
+                stop_id, parent_station
+            ),
+            Self::MissingRequiredParent {
+                stop_id,
+                location_type,
+            } => write!(
+                f,
+                "Stop {} of type {:?} requires a parent station",
+                stop_id, location_type
+            ),
+            Self::IllegalParent {
+                stop_id,
+                location_type,
+                parent_station,
+                parent_location_type,
+            } => write!(
+                f,
+                "Stop {} of type {:?} can't have parent {} of type {:?}",
+                stop_id, location_type, parent_station, parent_location_type
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HierarchyError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A departure from any platform of a station
+pub struct StationDeparture {
+    pub trip_id: String,
+    pub route_id: String,
+    pub stop_id: String,
+    pub departure_time: GtfsTime,
+    pub headsign: Option<String>,
+}
+
+impl Schedule {
+    /// The stop's direct parent, if it has one and it exists
+    pub fn parent_of(&self, stop_id: &str) -> Option<&Stop> {
+        self.stops
+            .get(self.stops.get(stop_id)?.parent_station.as_deref()?)
+    }
+
+    /// Direct children of a stop, ordered by stop_id. For a station these are its platforms,
+    /// entrances and generic nodes, for a platform its boarding areas
+    pub fn children(&self, stop_id: &str) -> Vec<&Stop> {
+        let mut children: Vec<&Stop> = self
+            .stops
+            .values()
+            .filter(|s| s.parent_station.as_deref() == Some(stop_id))
+            .collect();
+
+        children.sort_by(|a, b| a.stop_id.cmp(&b.stop_id));
+        children
+    }
+
+    /// The station a location belongs to: itself for stations, its parent for platforms, entrances
+    /// and nodes, and its grandparent for boarding areas
+    pub fn station_of(&self, stop_id: &str) -> Option<&Stop> {
+        let mut stop = self.stops.get(stop_id)?;
+        // Hierarchies are at most three levels deep
+        for _ in 0..2 {
+            if stop.location_type() == LocationType::Station {
+                break;
+            }
+            stop = self.stops.get(stop.parent_station.as_deref()?)?;
+        }
+
+        (stop.location_type() == LocationType::Station).then_some(stop)
+    }
+
+    /// Every station served by a route, ordered by stop_id. Stops without a parent station stand
+    /// in for their own station
+    pub fn route_stations(&self, route_id: &str) -> Vec<&Stop> {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stations: Vec<&Stop> = self
+            .trips
+            .values()
+            .filter(|t| t.route_id == route_id)
+            .filter_map(|t| self.stop_times.get(&t.trip_id))
+            .flatten()
+            .filter_map(|st| self.stops.get(st.stop_id.as_deref()?))
+            .map(|stop| self.station_of(&stop.stop_id).unwrap_or(stop))
+            .filter(|station| seen.insert(&station.stop_id))
+            .collect();
+
+        stations.sort_by(|a, b| a.stop_id.cmp(&b.stop_id));
+        stations
+    }
+
+    /// Every departure from any platform of a station on `date`, ordered by time. A trip's last
+    /// stop, stops without pickups and stop times without a departure_time aren't departures
+    pub fn station_departures(&self, station_id: &str, date: ServiceDate) -> Vec<StationDeparture> {
+        let platforms: HashSet<&str> = self
+            .stops
+            .keys()
+            .map(String::as_str)
+            .filter(|id| {
+                *id == station_id
+                    || self
+                        .station_of(id)
+                        .is_some_and(|station| station.stop_id == station_id)
+            })
+            .collect();
+        let platforms = &platforms;
+
+        let mut departures: Vec<StationDeparture> = self
+            .trips
+            .values()
+            .filter(|t| self.is_service_active(&t.service_id, date))
+            .filter_map(|t| Some((t, self.stop_times.get(&t.trip_id)?)))
+            .flat_map(|(trip, stop_times)| {
+                // Trips only arrive at their last stop
+                let departing = &stop_times[..stop_times.len().saturating_sub(1)];
+                departing.iter().filter_map(move |st| {
+                    let stop_id = st.stop_id.as_deref()?;
+                    if !platforms.contains(stop_id) || st.pickup_type == Some(PickupType::NoPickup)
+                    {
+                        return None;
+                    }
+
+                    Some(StationDeparture {
+                        trip_id: trip.trip_id.clone(),
+                        route_id: trip.route_id.clone(),
+                        stop_id: stop_id.to_owned(),
+                        departure_time: st.departure_time?,
+                        headsign: st
+                            .stop_headsign
+                            .clone()
+                            .or_else(|| trip.trip_headsign.clone()),
+                    })
+                })
+            })
+            .collect();
+
+        departures
+            .sort_by(|a, b| (a.departure_time, &a.trip_id).cmp(&(b.departure_time, &b.trip_id)));
+        departures
+    }
+
+    /// Checks that every parent_station exists and that each location type has a legal parent, as
+    /// described in the spec for stops.txt. Errors are ordered by stop_id
+    pub fn validate_station_hierarchy(&self) -> Vec<HierarchyError> {
+        let mut errors: Vec<HierarchyError> = Vec::new();
+
+        for stop in self.stops.values() {
+            let location_type = stop.location_type();
+
+            let Some(parent_station) = &stop.parent_station else {
+                if matches!(
+                    location_type,
+                    LocationType::EntranceExit
+                        | LocationType::GenericNode
+                        | LocationType::BoardingArea
+                ) {
+                    errors.push(HierarchyError::MissingRequiredParent {
+                        stop_id: stop.stop_id.clone(),
+                        location_type,
+                    });
+                }
+                continue;
+            };
+
+            let Some(parent) = self.stops.get(parent_station) else {
+                errors.push(HierarchyError::MissingParent {
+                    stop_id: stop.stop_id.clone(),
+                    parent_station: parent_station.clone(),
+                });
+                continue;
+            };
+
+            let parent_location_type = parent.location_type();
+            let legal = match location_type {
+                LocationType::Station => false,
+                LocationType::StopPlatform
+                | LocationType::EntranceExit
+                | LocationType::GenericNode => parent_location_type == LocationType::Station,
+                LocationType::BoardingArea => parent_location_type == LocationType::StopPlatform,
+                // The spec doesn't say where these can go
+                LocationType::Unknown(_) => true,
+            };
+            if !legal {
+                errors.push(HierarchyError::IllegalParent {
+                    stop_id: stop.stop_id.clone(),
+                    location_type,
+                    parent_station: parent_station.clone(),
+                    parent_location_type,
+                });
+            }
+        }
+
+        errors.sort_by(|a, b| stop_id(a).cmp(stop_id(b)));
+        errors
+    }
+}
+
+fn stop_id(error: &HierarchyError) -> &str {
+    match error {
+        HierarchyError::MissingParent { stop_id, .. }
+        | HierarchyError::MissingRequiredParent { stop_id, .. }
+        | HierarchyError::IllegalParent { stop_id, .. } => stop_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::{ParseContext, ScheduleLoader, parse_stops};
+
+    fn schedule() -> Schedule {
+        let stops = "stop_id,stop_name,location_type,parent_station\n\
+            S31,Tompkinsville,1,\n\
+            S31N,Tompkinsville,0,S31\n\
+            S31S,Tompkinsville,0,S31\n\
+            S30,Stapleton,1,\n\
+            S30N,Stapleton,,S30\n\
+            S30S,Stapleton,,S30\n\
+            S29,Clifton,,\n";
+        let trips = "route_id,trip_id,service_id,trip_headsign\n\
+            SI,north_1,Weekday,St George\n\
+            SI,north_2,Weekday,St George\n\
+            SI,north_3,Weekday,St George\n\
+            SI,north_4,Weekday,St George\n\
+            SI,south_1,Weekend,Tottenville\n";
+        // north_1 terminates at Tompkinsville, and north_3 only drops off there
+        let stop_times = "trip_id,departure_time,stop_id,stop_sequence,stop_headsign,pickup_type\n\
+            north_1,08:00:00,S29,1,,\n\
+            north_1,08:02:00,S30N,2,,\n\
+            north_1,08:05:00,S31N,3,,\n\
+            north_2,07:50:00,S31N,1,Ferry,\n\
+            north_2,07:55:00,S30N,2,,\n\
+            north_3,08:10:00,S31N,1,,1\n\
+            north_3,08:15:00,S30N,2,,\n\
+            north_4,08:30:00,S31N,1,,0\n\
+            north_4,08:35:00,S30N,2,,\n\
+            south_1,09:00:00,S31S,1,,\n\
+            south_1,09:03:00,S30S,2,,\n";
+        let services = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n\
+            Weekday,1,1,1,1,1,0,0,20250101,20251231\n\
+            Weekend,0,0,0,0,0,1,1,20250101,20251231\n";
+
+        ScheduleLoader::new()
+            .file("stops.txt", stops.as_bytes())
+            .file("calendar.txt", services.as_bytes())
+            .file("trips.txt", trips.as_bytes())
+            .file("stop_times.txt", stop_times.as_bytes())
+            .skip("agency.txt")
+            .skip("routes.txt")
+            .load()
+            .unwrap()
+    }
+
+    fn ids(stops: Vec<&Stop>) -> Vec<&str> {
+        stops.iter().map(|s| s.stop_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_navigation() {
+        let schedule = schedule();
+
+        assert_eq!(ids(schedule.children("S31")), vec!["S31N", "S31S"]);
+        assert!(schedule.children("S31N").is_empty());
+        assert_eq!(
+            schedule.parent_of("S31N").map(|s| s.stop_id.as_str()),
+            Some("S31")
+        );
+        assert!(schedule.parent_of("S31").is_none());
+
+        assert_eq!(
+            schedule.station_of("S30N").map(|s| s.stop_id.as_str()),
+            Some("S30")
+        );
+        assert_eq!(
+            schedule.station_of("S31").map(|s| s.stop_id.as_str()),
+            Some("S31")
+        );
+        // Stops without a station
+        assert!(schedule.station_of("S29").is_none());
+        assert!(schedule.station_of("S99").is_none());
+
+        assert_eq!(
+            ids(schedule.route_stations("SI")),
+            vec!["S29", "S30", "S31"]
+        );
+        assert!(schedule.route_stations("1").is_empty());
+    }
+
+    #[test]
+    fn test_station_departures() {
+        let schedule = schedule();
+
+        let monday: ServiceDate = "20250217".parse().unwrap();
+        let departures = schedule.station_departures("S31", monday);
+        assert_eq!(
+            departures
+                .iter()
+                .map(|d| (d.trip_id.as_str(), d.stop_id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("north_2", "S31N"), ("north_4", "S31N")]
+        );
+        assert_eq!(departures[0].headsign, Some("Ferry".to_owned()));
+        assert_eq!(departures[1].headsign, Some("St George".to_owned()));
+        assert_eq!(departures[1].departure_time, GtfsTime::from_hms(8, 30, 0));
+
+        let saturday = monday + 5;
+        let departures = schedule.station_departures("S31", saturday);
+        assert_eq!(departures.len(), 1);
+        assert_eq!(departures[0].stop_id, "S31S");
+    }
+
+    #[test]
+    fn test_validate_station_hierarchy() {
+        let mut schedule = schedule();
+        assert!(schedule.validate_station_hierarchy().is_empty());
+
+        let stops = "stop_id,location_type,parent_station\n\
+            S31_E1,2,\n\
+            S31N_B,4,S31\n\
+            S32,1,S31\n\
+            S33N,0,S33\n";
+        schedule
+            .stops
+            .extend(parse_stops(stops.as_bytes(), &mut ParseContext::strict()).unwrap());
+
+        let errors = schedule.validate_station_hierarchy();
+        assert_eq!(
+            errors,
+            vec![
+                HierarchyError::IllegalParent {
+                    stop_id: "S31N_B".to_owned(),
+                    location_type: LocationType::BoardingArea,
+                    parent_station: "S31".to_owned(),
+                    parent_location_type: LocationType::Station,
+                },
+                HierarchyError::MissingRequiredParent {
+                    stop_id: "S31_E1".to_owned(),
+                    location_type: LocationType::EntranceExit,
+                },
+                HierarchyError::IllegalParent {
+                    stop_id: "S32".to_owned(),
+                    location_type: LocationType::Station,
+                    parent_station: "S31".to_owned(),
+                    parent_location_type: LocationType::Station,
+                },
+                HierarchyError::MissingParent {
+                    stop_id: "S33N".to_owned(),
+                    parent_station: "S33".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(
+            errors[3].to_string(),
+            "Stop S33N refers to missing parent station S33"
+        );
+    }
+}
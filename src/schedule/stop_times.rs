@@ -1,8 +1,15 @@
-use std::marker::PhantomData;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    marker::PhantomData,
+};
 
 use serde::Deserialize;
 
-use super::{stops::Stop, trips::Trip};
+use super::{
+    ParseContext, ParseDiagnostic, ParseMode, Result, Schedule, records::Records, stops::Stop,
+    time::GtfsTime, trips::Trip,
+};
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
 #[serde(from = "u32")]
@@ -11,6 +18,7 @@ pub enum DropoffType {
     NoDropoff,
     CallAgency,
     CallDriver,
+    Unknown(u32),
 }
 
 impl From<u32> for DropoffType {
@@ -20,7 +28,19 @@ impl From<u32> for DropoffType {
             1 => Self::NoDropoff,
             2 => Self::CallAgency,
             3 => Self::CallDriver,
-            _ => panic!("Invalid DropoffType: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<DropoffType> for u32 {
+    fn from(value: DropoffType) -> Self {
+        match value {
+            DropoffType::Dropoff => 0,
+            DropoffType::NoDropoff => 1,
+            DropoffType::CallAgency => 2,
+            DropoffType::CallDriver => 3,
+            DropoffType::Unknown(value) => value,
         }
     }
 }
@@ -32,6 +52,7 @@ pub enum PickupType {
     NoPickup,
     CallAgency,
     CallDriver,
+    Unknown(u32),
 }
 
 impl From<u32> for PickupType {
@@ -41,7 +62,19 @@ impl From<u32> for PickupType {
             1 => Self::NoPickup,
             2 => Self::CallAgency,
             3 => Self::CallDriver,
-            _ => panic!("Invalid PickupType: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<PickupType> for u32 {
+    fn from(value: PickupType) -> Self {
+        match value {
+            PickupType::Pickup => 0,
+            PickupType::NoPickup => 1,
+            PickupType::CallAgency => 2,
+            PickupType::CallDriver => 3,
+            PickupType::Unknown(value) => value,
         }
     }
 }
@@ -51,6 +84,7 @@ impl From<u32> for PickupType {
 pub enum Timepoint {
     Approximate,
     Precise,
+    Unknown(u32),
 }
 
 impl From<u32> for Timepoint {
@@ -58,7 +92,17 @@ impl From<u32> for Timepoint {
         match value {
             0 => Self::Approximate,
             1 => Self::Precise,
-            _ => panic!("Invalid Timepoint: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<Timepoint> for u32 {
+    fn from(value: Timepoint) -> Self {
+        match value {
+            Timepoint::Approximate => 0,
+            Timepoint::Precise => 1,
+            Timepoint::Unknown(value) => value,
         }
     }
 }
@@ -66,15 +110,15 @@ impl From<u32> for Timepoint {
 #[derive(Debug, Deserialize, Clone)]
 pub struct StopTime {
     pub trip_id: String,
-    pub arrival_time: Option<String>,
-    pub departure_time: Option<String>,
+    pub arrival_time: Option<GtfsTime>,
+    pub departure_time: Option<GtfsTime>,
     pub stop_id: Option<String>,
     pub location_group_id: Option<String>,
     pub location_id: Option<String>,
     pub stop_sequence: u32,
     pub stop_headsign: Option<String>,
-    pub start_pickup_drop_off_window: Option<String>,
-    pub end_pickup_drop_off_window: Option<String>,
+    pub start_pickup_drop_off_window: Option<GtfsTime>,
+    pub end_pickup_drop_off_window: Option<GtfsTime>,
     pub pickup_type: Option<PickupType>,
     pub drop_off_type: Option<DropoffType>,
     pub continuous_pickup: Option<PickupType>,
@@ -85,11 +129,239 @@ pub struct StopTime {
     pub drop_off_booking_rule_id: Option<String>,
 }
 
+/// A `StopTime` borrowing its strings from the `csv::ByteRecord` it was deserialized from, see
+/// `StopRef`
+#[derive(Debug, Deserialize, Clone)]
+pub struct StopTimeRef<'a> {
+    pub trip_id: &'a str,
+    pub arrival_time: Option<GtfsTime>,
+    pub departure_time: Option<GtfsTime>,
+    #[serde(borrow)]
+    pub stop_id: Option<&'a str>,
+    #[serde(borrow)]
+    pub location_group_id: Option<&'a str>,
+    #[serde(borrow)]
+    pub location_id: Option<&'a str>,
+    pub stop_sequence: u32,
+    #[serde(borrow)]
+    pub stop_headsign: Option<&'a str>,
+    pub start_pickup_drop_off_window: Option<GtfsTime>,
+    pub end_pickup_drop_off_window: Option<GtfsTime>,
+    pub pickup_type: Option<PickupType>,
+    pub drop_off_type: Option<DropoffType>,
+    pub continuous_pickup: Option<PickupType>,
+    pub continuous_drop_off: Option<DropoffType>,
+    pub shape_dist_traveled: Option<f64>,
+    pub timepoint: Option<Timepoint>,
+    #[serde(borrow)]
+    pub pickup_booking_rule_id: Option<&'a str>,
+    #[serde(borrow)]
+    pub drop_off_booking_rule_id: Option<&'a str>,
+}
+
+impl From<StopTimeRef<'_>> for StopTime {
+    fn from(st: StopTimeRef) -> Self {
+        Self {
+            trip_id: st.trip_id.to_owned(),
+            arrival_time: st.arrival_time,
+            departure_time: st.departure_time,
+            stop_id: st.stop_id.map(str::to_owned),
+            location_group_id: st.location_group_id.map(str::to_owned),
+            location_id: st.location_id.map(str::to_owned),
+            stop_sequence: st.stop_sequence,
+            stop_headsign: st.stop_headsign.map(str::to_owned),
+            start_pickup_drop_off_window: st.start_pickup_drop_off_window,
+            end_pickup_drop_off_window: st.end_pickup_drop_off_window,
+            pickup_type: st.pickup_type,
+            drop_off_type: st.drop_off_type,
+            continuous_pickup: st.continuous_pickup,
+            continuous_drop_off: st.continuous_drop_off,
+            shape_dist_traveled: st.shape_dist_traveled,
+            timepoint: st.timepoint,
+            pickup_booking_rule_id: st.pickup_booking_rule_id.map(str::to_owned),
+            drop_off_booking_rule_id: st.drop_off_booking_rule_id.map(str::to_owned),
+        }
+    }
+}
+
+impl Schedule {
+    /// Stop times of a trip ordered by stop_sequence, empty for unknown trips
+    pub fn trip_stops(&self, trip_id: &str) -> &[StopTime] {
+        self.stop_times.get(trip_id).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn stop_time(&self, trip_id: &str, stop_sequence: u32) -> Option<&StopTime> {
+        let stops = self.trip_stops(trip_id);
+        let i = stops
+            .binary_search_by_key(&stop_sequence, |st| st.stop_sequence)
+            .ok()?;
+
+        Some(&stops[i])
+    }
+
+    /// The stop a trip makes after `stop_sequence`. Sequences needn't be consecutive, and
+    /// `stop_sequence` needn't be one the trip actually has
+    pub fn next_stop(&self, trip_id: &str, stop_sequence: u32) -> Option<&StopTime> {
+        let stops = self.trip_stops(trip_id);
+
+        stops.get(stops.partition_point(|st| st.stop_sequence <= stop_sequence))
+    }
+
+    /// Stop times of a trip from `from_seq` to `to_seq`, both inclusive
+    pub fn segment(&self, trip_id: &str, from_seq: u32, to_seq: u32) -> &[StopTime] {
+        let stops = self.trip_stops(trip_id);
+        let start = stops.partition_point(|st| st.stop_sequence < from_seq);
+        let end = stops.partition_point(|st| st.stop_sequence <= to_seq);
+
+        stops.get(start..end).unwrap_or(&[])
+    }
+}
+
+/// Lazily reads stop_times.txt one record at a time, so memory stays bounded however large the
+/// file is. Filters combine, a stop time has to pass all of them
+pub struct StopTimeIter<R> {
+    records: Records<StopTime, R>,
+    ctx: ParseContext,
+    trip_ids: Option<HashSet<String>>,
+    stop_ids: Option<HashSet<String>>,
+}
+
+impl<R> StopTimeIter<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Self {
+        Self {
+            records: Records::new(reader, "stop_times.txt"),
+            ctx: ParseContext::strict(),
+            trip_ids: None,
+            stop_ids: None,
+        }
+    }
+
+    /// In lenient mode records that can't be parsed are skipped and kept in `diagnostics`, in
+    /// strict mode they're yielded as errors
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.ctx.mode = mode;
+        self
+    }
+
+    /// Only yield stop times of these trips
+    pub fn trips<I, S>(mut self, trip_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let trip_ids: HashSet<String> = trip_ids.into_iter().map(Into::into).collect();
+        self.trip_ids = Some(match self.trip_ids.take() {
+            Some(current) => current.intersection(&trip_ids).cloned().collect(),
+            None => trip_ids,
+        });
+        self
+    }
+
+    /// Only yield stop times of trips on these routes, looked up in `trips`
+    pub fn routes<'a, I>(self, route_ids: I, trips: &HashMap<String, Trip>) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let route_ids: HashSet<&str> = route_ids.into_iter().collect();
+        let trip_ids = trips
+            .values()
+            .filter(|t| route_ids.contains(t.route_id.as_str()))
+            .map(|t| t.trip_id.clone());
+
+        self.trips(trip_ids)
+    }
+
+    /// Only yield stop times at these stops
+    pub fn stops<I, S>(mut self, stop_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.stop_ids = Some(stop_ids.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Records skipped so far in lenient mode
+    pub fn diagnostics(&self) -> &[ParseDiagnostic] {
+        &self.ctx.diagnostics
+    }
+
+    fn matches(&self, st: &StopTime) -> bool {
+        self.trip_ids
+            .as_ref()
+            .is_none_or(|ids| ids.contains(&st.trip_id))
+            && self.stop_ids.as_ref().is_none_or(|ids| {
+                st.stop_id
+                    .as_ref()
+                    .is_some_and(|stop_id| ids.contains(stop_id))
+            })
+    }
+}
+
+impl<R> Iterator for StopTimeIter<R>
+where
+    R: Read,
+{
+    type Item = Result<StopTime>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let rec = self.records.next()?;
+            match self.ctx.accept(rec) {
+                Ok(Some(st)) if self.matches(&st) => return Some(Ok(st)),
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
 
     use super::*;
+    use crate::schedule::{Error, ParseContext, ParseError, parse_stop_times};
+
+    #[test]
+    fn test_stop_time_ref() -> Result<(), csv::Error> {
+        let path = PathBuf::from("./test_data/schedule/stop_times_abbrev.txt");
+        let mut reader = csv::Reader::from_path(&path)?;
+        let headers = reader.byte_headers()?.clone();
+        let mut record = csv::ByteRecord::new();
+        let mut owned = csv::Reader::from_path(path)?.into_deserialize::<StopTime>();
+        let mut count = 0;
+
+        while reader.read_byte_record(&mut record)? {
+            let st: StopTimeRef = record.deserialize(Some(&headers))?;
+            let expected = owned.next().unwrap()?;
+            assert_eq!(st.trip_id, expected.trip_id);
+            assert_eq!(st.stop_id, expected.stop_id.as_deref());
+            assert_eq!(st.arrival_time, expected.arrival_time);
+            assert_eq!(st.stop_sequence, expected.stop_sequence);
+            assert_eq!(StopTime::from(st).stop_headsign, expected.stop_headsign);
+            count += 1;
+        }
+        assert_eq!(count, 10000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_timepoint_codes() -> Result<(), csv::Error> {
+        let data = "trip_id,stop_id,stop_sequence,timepoint\na,101,1,1\na,103,2,4\n";
+        let res: Vec<StopTime> = csv::Reader::from_reader(data.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()?;
+
+        assert_eq!(res[0].timepoint, Some(Timepoint::Precise));
+        assert_eq!(res[1].timepoint, Some(Timepoint::Unknown(4)));
+
+        Ok(())
+    }
 
     #[test]
     fn test_stop_times() -> Result<(), csv::Error> {
@@ -106,8 +378,8 @@ mod tests {
         let mta = res.pop().unwrap();
 
         assert_eq!(mta.trip_id, "AFA24GEN-1038-Sunday-00_123700_1..N03R");
-        assert_eq!(mta.arrival_time, Some("20:44:00".to_owned()));
-        assert_eq!(mta.departure_time, Some("20:44:00".to_owned()));
+        assert_eq!(mta.arrival_time, Some(GtfsTime::from_hms(20, 44, 0)));
+        assert_eq!(mta.departure_time, Some(GtfsTime::from_hms(20, 44, 0)));
         assert_eq!(mta.stop_id, Some("135N".to_owned()));
         assert_eq!(mta.location_group_id, None);
         assert_eq!(mta.location_id, None);
@@ -126,4 +398,104 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_trip_stops() -> Result<(), Error> {
+        let trips = "route_id,trip_id,service_id\n1,a,Weekday\n1,b,Weekday\n";
+        // Out of order, with a gap in the sequence and a duplicate of stop 4
+        let stop_times = "trip_id,stop_id,stop_sequence\na,103,3\na,101,1\na,107,10\na,105,4\nb,101,1\na,104,4\n";
+
+        let mut trip_map: HashMap<String, Trip> = HashMap::new();
+        for trip in csv::Reader::from_reader(trips.as_bytes()).deserialize() {
+            let trip: Trip = trip.unwrap();
+            trip_map.insert(trip.trip_id.clone(), trip);
+        }
+        let schedule = Schedule {
+            stop_times: parse_stop_times(
+                stop_times.as_bytes(),
+                &trip_map,
+                &mut ParseContext::strict(),
+            )?,
+            ..Default::default()
+        };
+
+        let sequences =
+            |stops: &[StopTime]| stops.iter().map(|st| st.stop_sequence).collect::<Vec<_>>();
+
+        assert_eq!(sequences(schedule.trip_stops("a")), vec![1, 3, 4, 10]);
+        assert!(schedule.trip_stops("c").is_empty());
+
+        assert_eq!(
+            schedule
+                .stop_time("a", 4)
+                .and_then(|st| st.stop_id.as_deref()),
+            Some("104")
+        );
+        assert!(schedule.stop_time("a", 2).is_none());
+
+        assert_eq!(
+            schedule.next_stop("a", 1).map(|st| st.stop_sequence),
+            Some(3)
+        );
+        assert_eq!(
+            schedule.next_stop("a", 5).map(|st| st.stop_sequence),
+            Some(10)
+        );
+        assert!(schedule.next_stop("a", 10).is_none());
+        assert!(schedule.next_stop("b", 1).is_none());
+
+        assert_eq!(sequences(schedule.segment("a", 3, 10)), vec![3, 4, 10]);
+        assert_eq!(sequences(schedule.segment("a", 2, 4)), vec![3, 4]);
+        assert!(schedule.segment("a", 4, 3).is_empty());
+        assert!(schedule.segment("c", 1, 10).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_time_iter() -> Result<(), Error> {
+        let trips = "route_id,trip_id,service_id\n1,a,Weekday\n2,b,Weekday\n1,c,Weekday\n";
+        let stop_times =
+            "trip_id,stop_id,stop_sequence\na,101,1\na,103,2\nb,201,1\nc,101,1\nc,103,2\n";
+
+        let mut trip_map: HashMap<String, Trip> = HashMap::new();
+        for trip in csv::Reader::from_reader(trips.as_bytes()).deserialize() {
+            let trip: Trip = trip.unwrap();
+            trip_map.insert(trip.trip_id.clone(), trip);
+        }
+
+        let ids = |iter: StopTimeIter<&[u8]>| -> Result<Vec<String>, Error> {
+            iter.map(|st| st.map(|st| format!("{}/{}", st.trip_id, st.stop_sequence)))
+                .collect()
+        };
+
+        let iter = StopTimeIter::new(stop_times.as_bytes()).trips(["a"]);
+        assert_eq!(ids(iter)?, vec!["a/1", "a/2"]);
+
+        let iter = StopTimeIter::new(stop_times.as_bytes()).routes(["1"], &trip_map);
+        assert_eq!(ids(iter)?, vec!["a/1", "a/2", "c/1", "c/2"]);
+
+        let iter = StopTimeIter::new(stop_times.as_bytes())
+            .routes(["1"], &trip_map)
+            .trips(["c", "b"])
+            .stops(["103"]);
+        assert_eq!(ids(iter)?, vec!["c/2"]);
+
+        // Strict mode stops at the bad record, even if it would be filtered out
+        let stop_times = "trip_id,stop_id,stop_sequence\na,101,1\nb,x,y\na,103,2\n";
+        let err = ids(StopTimeIter::new(stop_times.as_bytes()).trips(["a"])).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse(ParseError {
+                record: Some(2),
+                ..
+            })
+        ));
+
+        let mut iter = StopTimeIter::new(stop_times.as_bytes()).mode(ParseMode::Lenient);
+        assert_eq!(iter.by_ref().filter_map(Result::ok).count(), 2);
+        assert_eq!(iter.diagnostics().len(), 1);
+
+        Ok(())
+    }
 }
@@ -1,5 +1,7 @@
 use serde::Deserialize;
 
+use crate::geo::{self, LatLon};
+
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
 #[serde(from = "u32")]
 pub enum LocationType {
@@ -8,6 +10,7 @@ pub enum LocationType {
     EntranceExit,
     GenericNode,
     BoardingArea,
+    Unknown(u32),
 }
 
 impl From<u32> for LocationType {
@@ -18,7 +21,20 @@ impl From<u32> for LocationType {
             2 => Self::EntranceExit,
             3 => Self::GenericNode,
             4 => Self::BoardingArea,
-            _ => panic!("Invalid LocationType: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<LocationType> for u32 {
+    fn from(value: LocationType) -> Self {
+        match value {
+            LocationType::StopPlatform => 0,
+            LocationType::Station => 1,
+            LocationType::EntranceExit => 2,
+            LocationType::GenericNode => 3,
+            LocationType::BoardingArea => 4,
+            LocationType::Unknown(value) => value,
         }
     }
 }
@@ -29,6 +45,7 @@ pub enum WheelchairBoarding {
     NoInfo,
     SomeSupport,
     NoSupport,
+    Unknown(u32),
 }
 
 impl From<u32> for WheelchairBoarding {
@@ -37,20 +54,33 @@ impl From<u32> for WheelchairBoarding {
             0 => Self::NoInfo,
             1 => Self::SomeSupport,
             2 => Self::NoSupport,
-            _ => panic!("Invalid WheelchairBoarding: {}", value),
+            _ => Self::Unknown(value),
         }
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+impl From<WheelchairBoarding> for u32 {
+    fn from(value: WheelchairBoarding) -> Self {
+        match value {
+            WheelchairBoarding::NoInfo => 0,
+            WheelchairBoarding::SomeSupport => 1,
+            WheelchairBoarding::NoSupport => 2,
+            WheelchairBoarding::Unknown(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct Stop {
     pub stop_id: String,
     pub stop_code: Option<String>,
     pub stop_name: Option<String>,
     pub tts_stop_name: Option<String>,
     pub stop_desc: Option<String>,
-    pub stop_lat: Option<String>,
-    pub stop_lon: Option<String>,
+    #[serde(default, deserialize_with = "geo::deserialize_opt_lat")]
+    pub stop_lat: Option<f64>,
+    #[serde(default, deserialize_with = "geo::deserialize_opt_lon")]
+    pub stop_lon: Option<f64>,
     pub zone_id: Option<String>,
     pub stop_url: Option<String>,
     pub location_type: Option<LocationType>,
@@ -61,12 +91,121 @@ pub struct Stop {
     pub platform_code: Option<String>,
 }
 
+impl Stop {
+    /// Only present if both coordinates are, which the spec allows for generic nodes and boarding
+    /// areas
+    pub fn location(&self) -> Option<LatLon> {
+        Some(LatLon {
+            lat: self.stop_lat?,
+            lon: self.stop_lon?,
+        })
+    }
+
+    // An empty location_type means a stop or platform
+    pub fn location_type(&self) -> LocationType {
+        self.location_type
+            .clone()
+            .unwrap_or(LocationType::StopPlatform)
+    }
+}
+
+/// A `Stop` borrowing its strings from the record it was read from, for scanning a feed without
+/// allocating. Deserialize it from a `csv::ByteRecord`:
+///
+/// ```ignore
+/// let mut reader = csv::Reader::from_path("gtfs/stops.txt")?;
+/// let headers = reader.byte_headers()?.clone();
+/// let mut record = csv::ByteRecord::new();
+/// while reader.read_byte_record(&mut record)? {
+///     let stop: StopRef = record.deserialize(Some(&headers))?;
+/// }
+/// ```
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct StopRef<'a> {
+    pub stop_id: &'a str,
+    #[serde(borrow)]
+    pub stop_code: Option<&'a str>,
+    #[serde(borrow)]
+    pub stop_name: Option<&'a str>,
+    #[serde(borrow)]
+    pub tts_stop_name: Option<&'a str>,
+    #[serde(borrow)]
+    pub stop_desc: Option<&'a str>,
+    #[serde(default, deserialize_with = "geo::deserialize_opt_lat")]
+    pub stop_lat: Option<f64>,
+    #[serde(default, deserialize_with = "geo::deserialize_opt_lon")]
+    pub stop_lon: Option<f64>,
+    #[serde(borrow)]
+    pub zone_id: Option<&'a str>,
+    #[serde(borrow)]
+    pub stop_url: Option<&'a str>,
+    pub location_type: Option<LocationType>,
+    #[serde(borrow)]
+    pub parent_station: Option<&'a str>,
+    #[serde(borrow)]
+    pub stop_timezone: Option<&'a str>,
+    pub wheelchair_boarding: Option<WheelchairBoarding>,
+    #[serde(borrow)]
+    pub level_id: Option<&'a str>,
+    #[serde(borrow)]
+    pub platform_code: Option<&'a str>,
+}
+
+impl From<StopRef<'_>> for Stop {
+    fn from(stop: StopRef) -> Self {
+        Self {
+            stop_id: stop.stop_id.to_owned(),
+            stop_code: stop.stop_code.map(str::to_owned),
+            stop_name: stop.stop_name.map(str::to_owned),
+            tts_stop_name: stop.tts_stop_name.map(str::to_owned),
+            stop_desc: stop.stop_desc.map(str::to_owned),
+            stop_lat: stop.stop_lat,
+            stop_lon: stop.stop_lon,
+            zone_id: stop.zone_id.map(str::to_owned),
+            stop_url: stop.stop_url.map(str::to_owned),
+            location_type: stop.location_type,
+            parent_station: stop.parent_station.map(str::to_owned),
+            stop_timezone: stop.stop_timezone.map(str::to_owned),
+            wheelchair_boarding: stop.wheelchair_boarding,
+            level_id: stop.level_id.map(str::to_owned),
+            platform_code: stop.platform_code.map(str::to_owned),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
 
     use super::*;
 
+    #[test]
+    fn test_stop_ref() -> Result<(), csv::Error> {
+        let data = "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n101,\"Van Cortlandt Park, 242 St\",40.889248,-73.898583,1,\n101N,Van Cortlandt Park-242 St,40.889248,-73.898583,,101\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let headers = reader.byte_headers()?.clone();
+        let mut record = csv::ByteRecord::new();
+
+        assert!(reader.read_byte_record(&mut record)?);
+        let stop: StopRef = record.deserialize(Some(&headers))?;
+        assert_eq!(stop.stop_id, "101");
+        assert_eq!(stop.stop_name, Some("Van Cortlandt Park, 242 St"));
+        assert_eq!(stop.location_type, Some(LocationType::Station));
+        assert_eq!(stop.parent_station, None);
+        assert_eq!(stop.stop_code, None);
+
+        assert!(reader.read_byte_record(&mut record)?);
+        let stop: StopRef = record.deserialize(Some(&headers))?;
+        assert_eq!(stop.parent_station, Some("101"));
+
+        let owned = Stop::from(stop);
+        assert_eq!(owned.stop_id, "101N");
+        assert_eq!(owned.parent_station, Some("101".to_owned()));
+        assert_eq!(owned.location().map(|l| l.lat), Some(40.889248));
+
+        Ok(())
+    }
+
     #[test]
     fn test_stops() -> Result<(), csv::Error> {
         let path = PathBuf::from("./test_data/schedule/stops.txt");
@@ -86,8 +225,15 @@ mod tests {
         assert_eq!(mta.stop_name, Some("St George".to_owned()));
         assert_eq!(mta.tts_stop_name, None);
         assert_eq!(mta.stop_desc, None);
-        assert_eq!(mta.stop_lat, Some("40.643748".to_owned()));
-        assert_eq!(mta.stop_lon, Some("-74.073643".to_owned()));
+        assert_eq!(mta.stop_lat, Some(40.643748));
+        assert_eq!(mta.stop_lon, Some(-74.073643));
+        assert_eq!(
+            mta.location(),
+            Some(LatLon {
+                lat: 40.643748,
+                lon: -74.073643
+            })
+        );
         assert_eq!(mta.zone_id, None);
         assert_eq!(mta.stop_url, None);
         assert_eq!(mta.location_type, None);
@@ -99,4 +245,19 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_location_type_codes() -> Result<(), csv::Error> {
+        let data = "stop_id,location_type\n101,1\n101N,9\n";
+        let stops: Vec<Stop> = csv::Reader::from_reader(data.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()?;
+
+        assert_eq!(stops[0].location_type(), LocationType::Station);
+        // Unknown codes don't reject the row
+        assert_eq!(stops[1].location_type(), LocationType::Unknown(9));
+        assert_eq!(u32::from(LocationType::Unknown(9)), 9);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,219 @@
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Sub, SubAssign},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize, de::Visitor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+/// A GTFS time of day, stored as seconds since "noon minus 12h" on the service day. Trips that run
+/// past midnight use times like `25:30:00`, so this isn't bounded by 24 hours, and comparing two
+/// times from the same service day orders them correctly
+pub struct GtfsTime(u32);
+
+impl GtfsTime {
+    pub const fn from_secs(secs: u32) -> Self {
+        Self(secs)
+    }
+
+    pub const fn from_hms(hours: u32, minutes: u32, seconds: u32) -> Self {
+        Self(hours * 3600 + minutes * 60 + seconds)
+    }
+
+    pub const fn secs(self) -> u32 {
+        self.0
+    }
+
+    pub const fn hours(self) -> u32 {
+        self.0 / 3600
+    }
+
+    pub const fn minutes(self) -> u32 {
+        self.0 / 60 % 60
+    }
+
+    pub const fn seconds(self) -> u32 {
+        self.0 % 60
+    }
+
+    /// Whether this time falls on the calendar day after the service day, i.e. at or after 24:00:00
+    pub const fn is_next_day(self) -> bool {
+        self.0 >= 24 * 3600
+    }
+
+    pub fn checked_add(self, secs: u32) -> Option<Self> {
+        self.0.checked_add(secs).map(Self)
+    }
+
+    pub fn checked_sub(self, secs: u32) -> Option<Self> {
+        self.0.checked_sub(secs).map(Self)
+    }
+}
+
+impl FromStr for GtfsTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid GtfsTime: {}", s);
+
+        // Some feeds drop the leading zero on hours, e.g. "5:30:00"
+        let mut parts = s.trim().split(':');
+        let (Some(h), Some(m), Some(sec), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+
+        if h.is_empty() || m.len() != 2 || sec.len() != 2 {
+            return Err(invalid());
+        }
+
+        let h: u32 = h.parse().map_err(|_| invalid())?;
+        let m: u32 = m.parse().map_err(|_| invalid())?;
+        let sec: u32 = sec.parse().map_err(|_| invalid())?;
+
+        if m >= 60 || sec >= 60 {
+            return Err(invalid());
+        }
+
+        h.checked_mul(3600)
+            .and_then(|h| h.checked_add(m * 60 + sec))
+            .map(Self)
+            .ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for GtfsTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            self.hours(),
+            self.minutes(),
+            self.seconds()
+        )
+    }
+}
+
+impl Add<u32> for GtfsTime {
+    type Output = Self;
+
+    fn add(self, rhs: u32) -> Self::Output {
+        Self(self.0 + rhs)
+    }
+}
+
+impl AddAssign<u32> for GtfsTime {
+    fn add_assign(&mut self, rhs: u32) {
+        self.0 += rhs;
+    }
+}
+
+impl Sub<u32> for GtfsTime {
+    type Output = Self;
+
+    fn sub(self, rhs: u32) -> Self::Output {
+        Self(self.0 - rhs)
+    }
+}
+
+impl SubAssign<u32> for GtfsTime {
+    fn sub_assign(&mut self, rhs: u32) {
+        self.0 -= rhs;
+    }
+}
+
+impl Sub for GtfsTime {
+    // Signed, since the difference between two times can be negative
+    type Output = i64;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.0 as i64 - rhs.0 as i64
+    }
+}
+
+impl<'de> Deserialize<'de> for GtfsTime {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct GtfsTimeVisitor;
+
+        impl Visitor<'_> for GtfsTimeVisitor {
+            type Value = GtfsTime;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a time in HH:MM:SS format")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(GtfsTimeVisitor)
+    }
+}
+
+impl Serialize for GtfsTime {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("20:44:00".parse(), Ok(GtfsTime::from_hms(20, 44, 0)));
+        assert_eq!("5:03:09".parse(), Ok(GtfsTime::from_hms(5, 3, 9)));
+        assert_eq!("25:30:00".parse(), Ok(GtfsTime::from_secs(91800)));
+        assert_eq!("00:00:00".parse(), Ok(GtfsTime::default()));
+
+        for invalid in [
+            "",
+            "12:00",
+            "12:60:00",
+            "12:00:60",
+            "12:0:00",
+            "a:00:00",
+            "1:00:00:00",
+        ] {
+            assert!(invalid.parse::<GtfsTime>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(GtfsTime::from_hms(5, 3, 9).to_string(), "05:03:09");
+        assert_eq!(GtfsTime::from_hms(25, 30, 0).to_string(), "25:30:00");
+        assert_eq!(GtfsTime::from_hms(102, 0, 1).to_string(), "102:00:01");
+    }
+
+    #[test]
+    fn test_ordering_and_arithmetic() {
+        let before_midnight: GtfsTime = "23:58:00".parse().unwrap();
+        let after_midnight: GtfsTime = "24:03:30".parse().unwrap();
+
+        assert!(before_midnight < after_midnight);
+        assert!(!before_midnight.is_next_day());
+        assert!(after_midnight.is_next_day());
+        assert_eq!(after_midnight - before_midnight, 330);
+        assert_eq!(before_midnight - after_midnight, -330);
+        assert_eq!(before_midnight + 330, after_midnight);
+        assert_eq!(after_midnight - 330, before_midnight);
+        assert_eq!(before_midnight.checked_sub(86400), None);
+        assert_eq!(after_midnight.hours(), 24);
+        assert_eq!(after_midnight.minutes(), 3);
+        assert_eq!(after_midnight.seconds(), 30);
+    }
+}
@@ -9,6 +9,7 @@ pub enum TransferType {
     Impossible,
     InSeat,
     NoInSeat,
+    Unknown(u32),
 }
 
 impl From<u32> for TransferType {
@@ -20,7 +21,21 @@ impl From<u32> for TransferType {
             3 => Self::Impossible,
             4 => Self::InSeat,
             5 => Self::NoInSeat,
-            _ => panic!("Invalid TransferType: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<TransferType> for u32 {
+    fn from(value: TransferType) -> Self {
+        match value {
+            TransferType::Recommended => 0,
+            TransferType::Timed => 1,
+            TransferType::MinimumTime => 2,
+            TransferType::Impossible => 3,
+            TransferType::InSeat => 4,
+            TransferType::NoInSeat => 5,
+            TransferType::Unknown(value) => value,
         }
     }
 }
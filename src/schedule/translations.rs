@@ -0,0 +1,319 @@
+use serde::Deserialize;
+
+use super::{Schedule, agency};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Translation {
+    pub table_name: String,
+    pub field_name: String,
+    // IETF BCP 47 language code
+    pub language: String,
+    pub translation: String,
+    // Either record_id (plus record_sub_id for stop_times) or field_value identifies what is
+    // translated, feed_info uses neither
+    pub record_id: Option<String>,
+    pub record_sub_id: Option<String>,
+    pub field_value: Option<String>,
+}
+
+impl Schedule {
+    /// Original value of a translatable field. `record_sub_id` is the stop_sequence for stop_times
+    /// and ignored otherwise
+    pub fn field_value(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        record_id: &str,
+        record_sub_id: Option<&str>,
+    ) -> Option<&str> {
+        match table_name {
+            "agency" => {
+                // agency_id may be omitted only when the feed has one agency
+                let agency = if record_id.is_empty() {
+                    agency::sole_agency(&self.agencies)?
+                } else {
+                    self.agencies
+                        .iter()
+                        .find(|a| a.agency_id.as_deref() == Some(record_id))?
+                };
+
+                match field_name {
+                    "agency_name" => Some(&agency.agency_name),
+                    "agency_url" => Some(&agency.agency_url),
+                    "agency_phone" => agency.agency_phone.as_deref(),
+                    "agency_fare_url" => agency.agency_fare_url.as_deref(),
+                    "agency_email" => agency.agency_email.as_deref(),
+                    _ => None,
+                }
+            }
+            "stops" => {
+                let stop = self.stops.get(record_id)?;
+
+                match field_name {
+                    "stop_code" => stop.stop_code.as_deref(),
+                    "stop_name" => stop.stop_name.as_deref(),
+                    "tts_stop_name" => stop.tts_stop_name.as_deref(),
+                    "stop_desc" => stop.stop_desc.as_deref(),
+                    "stop_url" => stop.stop_url.as_deref(),
+                    "platform_code" => stop.platform_code.as_deref(),
+                    _ => None,
+                }
+            }
+            "routes" => {
+                let route = self.routes.get(record_id)?;
+
+                match field_name {
+                    "route_short_name" => route.route_short_name.as_deref(),
+                    "route_long_name" => route.route_long_name.as_deref(),
+                    "route_desc" => route.route_desc.as_deref(),
+                    "route_url" => route.route_url.as_deref(),
+                    _ => None,
+                }
+            }
+            "trips" => {
+                let trip = self.trips.get(record_id)?;
+
+                match field_name {
+                    "trip_headsign" => trip.trip_headsign.as_deref(),
+                    "trip_short_name" => trip.trip_short_name.as_deref(),
+                    _ => None,
+                }
+            }
+            "stop_times" => {
+                let stop_sequence: u32 = record_sub_id?.parse().ok()?;
+                let stop_time = self.stop_time(record_id, stop_sequence)?;
+
+                match field_name {
+                    "stop_headsign" => stop_time.stop_headsign.as_deref(),
+                    _ => None,
+                }
+            }
+            "pathways" => {
+                let pathway = self.pathways.get(record_id)?;
+
+                match field_name {
+                    "signposted_as" => pathway.signposted_as.as_deref(),
+                    "reversed_signposted_as" => pathway.reversed_signposted_as.as_deref(),
+                    _ => None,
+                }
+            }
+            "levels" => match field_name {
+                "level_name" => self.levels.get(record_id)?.level_name.as_deref(),
+                _ => None,
+            },
+            "attributions" => {
+                let attribution = self
+                    .attributions
+                    .iter()
+                    .find(|a| a.attribution_id.as_deref() == Some(record_id))?;
+
+                match field_name {
+                    "organization_name" => Some(&attribution.organization_name),
+                    "attribution_url" => attribution.attribution_url.as_deref(),
+                    "attribution_email" => attribution.attribution_email.as_deref(),
+                    "attribution_phone" => attribution.attribution_phone.as_deref(),
+                    _ => None,
+                }
+            }
+            "feed_info" => {
+                let feed_info = self.feed_info.as_ref()?;
+
+                match field_name {
+                    "feed_publisher_name" => Some(&feed_info.feed_publisher_name),
+                    "feed_publisher_url" => Some(&feed_info.feed_publisher_url),
+                    "feed_version" => feed_info.feed_version.as_deref(),
+                    "feed_contact_email" => feed_info.feed_contact_email.as_deref(),
+                    "feed_contact_url" => feed_info.feed_contact_url.as_deref(),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Translation of a field of one record, e.g. `translate("stops", "stop_name", "101", "es")`.
+    /// Translations by record_id take priority over translations by field_value
+    pub fn translate(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        record_id: &str,
+        language: &str,
+    ) -> Option<&str> {
+        self.translate_sub(table_name, field_name, record_id, None, language)
+    }
+
+    /// Like `translate`, for tables whose records need a record_sub_id, i.e. stop_times where
+    /// `record_id` is the trip_id and `record_sub_id` the stop_sequence
+    pub fn translate_sub(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        record_id: &str,
+        record_sub_id: Option<&str>,
+        language: &str,
+    ) -> Option<&str> {
+        let candidates = || {
+            self.translations
+                .get(table_name)
+                .into_iter()
+                .flatten()
+                .filter(|t| t.field_name == field_name && t.language.eq_ignore_ascii_case(language))
+        };
+
+        let by_record = candidates().find(|t| {
+            if table_name == "feed_info" {
+                t.record_id.is_none() && t.field_value.is_none()
+            } else {
+                t.record_id.as_deref() == Some(record_id)
+                    && t.record_sub_id.as_deref() == record_sub_id
+            }
+        });
+        if let Some(t) = by_record {
+            return Some(&t.translation);
+        }
+
+        let value = self.field_value(table_name, field_name, record_id, record_sub_id)?;
+        candidates()
+            .find(|t| t.field_value.as_deref() == Some(value))
+            .map(|t| t.translation.as_str())
+    }
+
+    /// Translation of a value wherever it appears in a field, using field_value translations only
+    pub fn translate_value(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        value: &str,
+        language: &str,
+    ) -> Option<&str> {
+        self.translations
+            .get(table_name)?
+            .iter()
+            .find(|t| {
+                t.field_name == field_name
+                    && t.language.eq_ignore_ascii_case(language)
+                    && t.field_value.as_deref() == Some(value)
+            })
+            .map(|t| t.translation.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::ScheduleLoader;
+
+    fn schedule() -> Schedule {
+        let stops = "stop_id,stop_name\n101,Van Cortlandt Park-242 St\n127,Times Sq-42 St\n127N,Times Sq-42 St\n";
+        let trips = "route_id,trip_id,service_id,trip_headsign\n1,a,Weekday,South Ferry\n";
+        let services = "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nWeekday,1,1,1,1,1,0,0,20250101,20251231\n";
+        let stop_times = "trip_id,stop_id,stop_sequence,stop_headsign\na,101,1,Downtown\n";
+        let agency = "agency_id,agency_name,agency_url,agency_timezone\nMTA NYCT,MTA New York City Transit,http://www.mta.info,America/New_York\n";
+        let feed_info =
+            "feed_publisher_name,feed_publisher_url,feed_lang\nMTA,http://www.mta.info,en\n";
+        let translations = "table_name,field_name,language,translation,record_id,record_sub_id,field_value\n\
+            stops,stop_name,es,Times Square-Calle 42,,,Times Sq-42 St\n\
+            stops,stop_name,zh,时代广场-42街,,,Times Sq-42 St\n\
+            stops,stop_name,es,Times Square (andén norte),127N,,\n\
+            trips,trip_headsign,es,South Ferry (sur),a,,\n\
+            stop_times,stop_headsign,es,Centro,a,1,\n\
+            feed_info,feed_publisher_name,es,Autoridad de Transporte Metropolitano,,,\n";
+
+        ScheduleLoader::new()
+            .file("agency.txt", agency.as_bytes())
+            .file("stops.txt", stops.as_bytes())
+            .file("calendar.txt", services.as_bytes())
+            .file("trips.txt", trips.as_bytes())
+            .file("stop_times.txt", stop_times.as_bytes())
+            .file("feed_info.txt", feed_info.as_bytes())
+            .file("translations.txt", translations.as_bytes())
+            .skip("routes.txt")
+            .load()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_translate() {
+        let schedule = schedule();
+
+        // By field_value
+        assert_eq!(
+            schedule.translate("stops", "stop_name", "127", "es"),
+            Some("Times Square-Calle 42")
+        );
+        assert_eq!(
+            schedule.translate("stops", "stop_name", "127", "zh"),
+            Some("时代广场-42街")
+        );
+        // record_id takes priority over field_value
+        assert_eq!(
+            schedule.translate("stops", "stop_name", "127N", "es"),
+            Some("Times Square (andén norte)")
+        );
+        assert_eq!(
+            schedule.translate("stops", "stop_name", "127N", "ZH"),
+            Some("时代广场-42街")
+        );
+        assert_eq!(schedule.translate("stops", "stop_name", "101", "es"), None);
+        assert_eq!(schedule.translate("stops", "stop_name", "127", "fr"), None);
+
+        assert_eq!(
+            schedule.translate("trips", "trip_headsign", "a", "es"),
+            Some("South Ferry (sur)")
+        );
+        assert_eq!(
+            schedule.translate_sub("stop_times", "stop_headsign", "a", Some("1"), "es"),
+            Some("Centro")
+        );
+        assert_eq!(
+            schedule.translate("feed_info", "feed_publisher_name", "", "es"),
+            Some("Autoridad de Transporte Metropolitano")
+        );
+
+        assert_eq!(
+            schedule.translate_value("stops", "stop_name", "Times Sq-42 St", "es"),
+            Some("Times Square-Calle 42")
+        );
+        assert_eq!(
+            schedule.translate_value("stops", "stop_name", "Van Cortlandt Park-242 St", "es"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_field_value() {
+        let schedule = schedule();
+
+        assert_eq!(
+            schedule.field_value("stops", "stop_name", "101", None),
+            Some("Van Cortlandt Park-242 St")
+        );
+        assert_eq!(
+            schedule.field_value("stop_times", "stop_headsign", "a", Some("1")),
+            Some("Downtown")
+        );
+        assert_eq!(
+            schedule.field_value("stop_times", "stop_headsign", "a", None),
+            None
+        );
+        assert_eq!(
+            schedule.field_value("feed_info", "feed_publisher_name", "", None),
+            Some("MTA")
+        );
+        assert_eq!(schedule.field_value("stops", "stop_lat", "101", None), None);
+
+        assert_eq!(
+            schedule.field_value("agency", "agency_name", "MTA NYCT", None),
+            Some("MTA New York City Transit")
+        );
+        assert_eq!(
+            schedule.field_value("agency", "agency_name", "", None),
+            Some("MTA New York City Transit")
+        );
+        assert_eq!(
+            schedule.field_value("agency", "agency_name", "MTABC", None),
+            None
+        );
+    }
+}
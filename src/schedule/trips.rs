@@ -8,6 +8,7 @@ use super::{Route, Service, ServiceException, ShapePoint};
 pub enum DirectionType {
     Uptown,
     Downtown,
+    Unknown(u32),
 }
 
 impl From<u32> for DirectionType {
@@ -15,7 +16,17 @@ impl From<u32> for DirectionType {
         match value {
             0 => Self::Uptown,
             1 => Self::Downtown,
-            _ => panic!("Invalid DirectionType: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<DirectionType> for u32 {
+    fn from(value: DirectionType) -> Self {
+        match value {
+            DirectionType::Uptown => 0,
+            DirectionType::Downtown => 1,
+            DirectionType::Unknown(value) => value,
         }
     }
 }
@@ -26,6 +37,7 @@ pub enum WheelchairAccessibility {
     NoInfo,
     SomeSupport,
     NoSupport,
+    Unknown(u32),
 }
 
 impl From<u32> for WheelchairAccessibility {
@@ -34,7 +46,18 @@ impl From<u32> for WheelchairAccessibility {
             0 => Self::NoInfo,
             1 => Self::SomeSupport,
             2 => Self::NoSupport,
-            _ => panic!("Invalid WheelchairAccessibility: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<WheelchairAccessibility> for u32 {
+    fn from(value: WheelchairAccessibility) -> Self {
+        match value {
+            WheelchairAccessibility::NoInfo => 0,
+            WheelchairAccessibility::SomeSupport => 1,
+            WheelchairAccessibility::NoSupport => 2,
+            WheelchairAccessibility::Unknown(value) => value,
         }
     }
 }
@@ -45,6 +68,7 @@ pub enum BikeSupport {
     NoInfo,
     SomeSupport,
     NoSupport,
+    Unknown(u32),
 }
 
 impl From<u32> for BikeSupport {
@@ -53,7 +77,18 @@ impl From<u32> for BikeSupport {
             0 => Self::NoInfo,
             1 => Self::SomeSupport,
             2 => Self::NoSupport,
-            _ => panic!("Invalid BikeSupport: {}", value),
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<BikeSupport> for u32 {
+    fn from(value: BikeSupport) -> Self {
+        match value {
+            BikeSupport::NoInfo => 0,
+            BikeSupport::SomeSupport => 1,
+            BikeSupport::NoSupport => 2,
+            BikeSupport::Unknown(value) => value,
         }
     }
 }
@@ -72,6 +107,43 @@ pub struct Trip {
     pub bikes_allowed: Option<BikeSupport>,
 }
 
+/// A `Trip` borrowing its strings from the `csv::ByteRecord` it was deserialized from, see
+/// `StopRef`
+#[derive(Debug, Deserialize, Clone)]
+pub struct TripRef<'a> {
+    pub trip_id: &'a str,
+    pub route_id: &'a str,
+    pub service_id: &'a str,
+    #[serde(borrow)]
+    pub trip_headsign: Option<&'a str>,
+    #[serde(borrow)]
+    pub trip_short_name: Option<&'a str>,
+    pub direction_id: Option<DirectionType>,
+    #[serde(borrow)]
+    pub block_id: Option<&'a str>,
+    #[serde(borrow)]
+    pub shape_id: Option<&'a str>,
+    pub wheelchair_accessible: Option<WheelchairAccessibility>,
+    pub bikes_allowed: Option<BikeSupport>,
+}
+
+impl From<TripRef<'_>> for Trip {
+    fn from(trip: TripRef) -> Self {
+        Self {
+            trip_id: trip.trip_id.to_owned(),
+            route_id: trip.route_id.to_owned(),
+            service_id: trip.service_id.to_owned(),
+            trip_headsign: trip.trip_headsign.map(str::to_owned),
+            trip_short_name: trip.trip_short_name.map(str::to_owned),
+            direction_id: trip.direction_id,
+            block_id: trip.block_id.map(str::to_owned),
+            shape_id: trip.shape_id.map(str::to_owned),
+            wheelchair_accessible: trip.wheelchair_accessible,
+            bikes_allowed: trip.bikes_allowed,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -101,4 +173,30 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_trip_codes() -> Result<(), csv::Error> {
+        let data = "route_id,trip_id,service_id,direction_id,wheelchair_accessible\n1,a,Weekday,1,2\n1,b,Weekday,2,7\n";
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let mut res: Vec<Trip> = Vec::new();
+
+        for rec in reader.deserialize() {
+            res.push(rec?);
+        }
+
+        assert_eq!(res[0].direction_id, Some(DirectionType::Downtown));
+        assert_eq!(
+            res[0].wheelchair_accessible,
+            Some(WheelchairAccessibility::NoSupport)
+        );
+        // Newer spec values are kept rather than failing the record
+        assert_eq!(res[1].direction_id, Some(DirectionType::Unknown(2)));
+        assert_eq!(
+            res[1].wheelchair_accessible,
+            Some(WheelchairAccessibility::Unknown(7))
+        );
+        assert_eq!(u32::from(DirectionType::Unknown(2)), 2);
+
+        Ok(())
+    }
 }
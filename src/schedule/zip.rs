@@ -1,73 +1,148 @@
 #![cfg(feature = "zip")]
 
-use std::io::{Cursor, Read, Seek};
+use std::io::{Read, Seek};
 
-use zip::ZipArchive;
+use zip::{ZipArchive, read::ZipFile, result::ZipError};
 
-use crate::schedule::{
-    parse_agencies, parse_routes, parse_service_exceptions, parse_services, parse_shapes,
-    parse_stop_times, parse_stops, parse_transfers, parse_trips,
+use crate::schedule::{ParseContext, ParseMode, Result, parse_feed_info};
+
+use super::{
+    Schedule,
+    date::ServiceDate,
+    source::{FeedSource, LoadOptions},
 };
 
-use super::Schedule;
+impl<R> FeedSource for ZipArchive<R>
+where
+    R: Read + Seek,
+{
+    type File<'a>
+        = ZipFile<'a, R>
+    where
+        R: 'a;
+
+    // Optional files are simply skipped when missing
+    fn open(&mut self, name: &'static str) -> Result<Option<ZipFile<'_, R>>> {
+        match self.by_name(name) {
+            Ok(file) => Ok(Some(file)),
+            Err(ZipError::FileNotFound) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
 
 impl Schedule {
     pub fn from_zip<R>(
-        mut zip: ZipArchive<R>,
-        date_bounds: Option<(&String, &String)>,
-    ) -> Option<Self>
+        zip: ZipArchive<R>,
+        date_bounds: Option<(ServiceDate, ServiceDate)>,
+        mode: ParseMode,
+    ) -> Result<Self>
     where
         R: Read + Seek,
     {
-        let agencies = parse_agencies(zip.by_name("agency.txt").ok()?);
-        let stops = parse_stops(zip.by_name("stops.txt").ok()?);
-        let services = parse_services(zip.by_name("calendar.txt").ok()?, date_bounds);
-        let service_exceptions =
-            parse_service_exceptions(zip.by_name("calendar_dates.txt").ok()?, date_bounds);
-        let routes = parse_routes(zip.by_name("routes.txt").ok()?);
-        let trips = parse_trips(
-            zip.by_name("trips.txt").ok()?,
-            &services,
-            &service_exceptions,
-        );
-        let shapes = parse_shapes(zip.by_name("shapes.txt").ok()?);
-        let transfers = parse_transfers(zip.by_name("transfers.txt").ok()?);
-        let stop_times = parse_stop_times(zip.by_name("stop_times.txt").ok()?, &trips);
-
-        Some(Self {
-            agencies,
-            stops,
-            services,
-            service_exceptions,
-            routes,
-            trips,
-            shapes,
-            transfers,
-            stop_times,
-        })
+        Self::from_source(
+            zip,
+            LoadOptions {
+                date_bounds,
+                mode,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Like `from_zip`, but bounded by the validity window in `feed_info.txt`. Falls back to no
+    /// bounds if the feed doesn't specify a full window
+    pub fn from_zip_in_feed_window<R>(mut zip: ZipArchive<R>, mode: ParseMode) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let date_bounds = match zip.open("feed_info.txt")? {
+            Some(file) => {
+                parse_feed_info(file, &mut ParseContext::new(mode))?.and_then(|f| f.date_bounds())
+            }
+            None => None,
+        };
+
+        Self::from_zip(zip, date_bounds, mode)
     }
 
-    pub fn all_from_zip<R>(mut zip: ZipArchive<R>) -> Option<Self>
+    pub fn all_from_zip<R>(mut zip: ZipArchive<R>) -> Result<Self>
     where
         R: Read + Seek,
     {
-        Self::from_zip(zip, None)
+        Self::from_zip(zip, None, ParseMode::default())
     }
 
-    pub fn one_day_from_zip<R>(mut zip: ZipArchive<R>, date: String) -> Option<Self>
+    pub fn one_day_from_zip<R>(mut zip: ZipArchive<R>, date: ServiceDate) -> Result<Self>
     where
         R: Read + Seek,
     {
-        Self::from_zip(zip, Some((&date, &date)))
+        Self::from_zip(zip, Some((date, date)), ParseMode::default())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{fs::File, io::Cursor};
-    use zip::ZipArchive;
+    use std::{
+        fs::File,
+        io::{Cursor, Write},
+    };
+    use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
 
-    use crate::schedule::Schedule;
+    use crate::schedule::{Error, ParseMode, Schedule};
+
+    const FEED: [(&str, &str); 11] = [
+        (
+            "agency.txt",
+            "agency_name,agency_url,agency_timezone\nMTA,http://www.mta.info,America/New_York\n",
+        ),
+        (
+            "feed_info.txt",
+            "feed_publisher_name,feed_publisher_url,feed_lang,feed_start_date,feed_end_date,feed_version\nMTA,http://www.mta.info,en,20250301,20250331,v1\n",
+        ),
+        (
+            "stops.txt",
+            "stop_id,stop_name\n101,Van Cortlandt Park-242 St\n",
+        ),
+        (
+            "calendar.txt",
+            "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\nMarch,1,1,1,1,1,1,1,20250301,20250331\nApril,1,1,1,1,1,1,1,20250401,20250430\n",
+        ),
+        ("routes.txt", "route_id,route_type\n1,1\n"),
+        (
+            "trips.txt",
+            "route_id,trip_id,service_id\n1,march,March\n1,april,April\n",
+        ),
+        (
+            "stop_times.txt",
+            "trip_id,stop_id,stop_sequence\nmarch,101,1\napril,101,1\n",
+        ),
+        (
+            "fare_attributes.txt",
+            "fare_id,price,currency_type,payment_method,transfers\nsubway,2.90,USD,1,\n",
+        ),
+        ("fare_rules.txt", "fare_id,route_id\nsubway,1\nexpress,2\n"),
+        (
+            "fare_products.txt",
+            "fare_product_id,amount,currency\nbase,2.90,USD\n",
+        ),
+        (
+            "fare_leg_rules.txt",
+            "leg_group_id,fare_product_id\nsubway,base\n",
+        ),
+    ];
+
+    fn feed_zip(skip: &[&str]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in FEED.iter().filter(|(name, _)| !skip.contains(name)) {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+
+        writer.finish_into_readable().unwrap()
+    }
 
     #[test]
     fn test_zip() {
@@ -76,5 +151,49 @@ mod tests {
                 .unwrap(),
         );
     }
-}
 
+    #[test]
+    fn test_zip_in_memory() {
+        let schedule = Schedule::all_from_zip(feed_zip(&[])).unwrap();
+
+        assert_eq!(schedule.trips.len(), 2);
+        assert_eq!(schedule.stop_times.len(), 2);
+        assert_eq!(
+            schedule.feed_info.and_then(|f| f.feed_version),
+            Some("v1".to_owned())
+        );
+
+        assert_eq!(schedule.fare_attributes.len(), 1);
+        // Rules for unknown fares are dropped
+        assert_eq!(schedule.fare_rules.len(), 1);
+        assert_eq!(
+            schedule.fare_rules["subway"][0].route_id,
+            Some("1".to_owned())
+        );
+
+        let schedule = Schedule::all_from_zip(feed_zip(&[
+            "feed_info.txt",
+            "fare_attributes.txt",
+            "fare_rules.txt",
+        ]))
+        .unwrap();
+        assert!(schedule.feed_info.is_none());
+        assert!(schedule.fare_attributes.is_empty() && schedule.fare_rules.is_empty());
+
+        let err = Schedule::all_from_zip(feed_zip(&["trips.txt"])).unwrap_err();
+        assert!(matches!(err, Error::MissingFile("trips.txt")));
+    }
+
+    #[test]
+    fn test_zip_in_feed_window() {
+        let schedule = Schedule::from_zip_in_feed_window(feed_zip(&[]), ParseMode::Strict).unwrap();
+
+        assert_eq!(schedule.trips.keys().collect::<Vec<_>>(), vec!["march"]);
+
+        let schedule =
+            Schedule::from_zip_in_feed_window(feed_zip(&["feed_info.txt"]), ParseMode::Strict)
+                .unwrap();
+
+        assert_eq!(schedule.trips.len(), 2);
+    }
+}